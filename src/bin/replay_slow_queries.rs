@@ -0,0 +1,65 @@
+// Replays a slow query log against the engine, offline:
+//   cargo run --bin replay-slow-queries -- slow_queries.jsonl
+// Row-level security comes from LUZMO_PLUGIN_POLICIES_FILE, as in the server.
+// The report goes to stdout on purpose.
+#![allow(clippy::print_stdout)]
+use std::path::Path;
+use std::process::ExitCode;
+
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::slowlog::{read_entries, replay};
+
+fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: replay-slow-queries <slow_queries.jsonl>");
+        return ExitCode::FAILURE;
+    };
+    let entries = match read_entries(Path::new(&path)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let policies = match RowPolicies::from_env() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (mut failed, mut skipped) = (0, 0);
+    println!("request_id                  dataset   recorded_ms  replay_ms     rows");
+    for entry in &entries {
+        if entry.redacted {
+            skipped += 1;
+            println!("{:<27} {:<9} {:>11.3} SKIPPED (redacted body)", entry.request_id, entry.dataset_id, entry.elapsed_ms);
+            continue;
+        }
+        match replay(entry, &policies) {
+            Ok(r) => println!(
+                "{:<27} {:<9} {:>11.3} {:>10.3} {:>8}",
+                entry.request_id, entry.dataset_id, entry.elapsed_ms, r.elapsed_ms, r.rows
+            ),
+            Err(e) => {
+                failed += 1;
+                println!("{:<27} {:<9} {:>11.3} ERROR {}", entry.request_id, entry.dataset_id, entry.elapsed_ms, e);
+            }
+        }
+    }
+    println!(
+        "{} queries replayed, {} failed, {} skipped",
+        entries.len() - skipped,
+        failed,
+        skipped
+    );
+
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
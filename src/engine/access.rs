@@ -0,0 +1,155 @@
+use std::env;
+use std::sync::OnceLock;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::engine::dataset::Schema;
+use crate::utils::credentials::Identity;
+
+// Column-level access: callers with one of `roles` see the column as-is,
+// everyone else gets the restriction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Restriction {
+    // Not listed in /datasets; referencing it in /query is an unknown column.
+    Hidden,
+    // Every value is replaced by MASK.
+    Masked,
+    // Values are replaced by a salted hash, so grouping and counting still work.
+    Hashed,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnAccess {
+    pub restriction: Restriction,
+    pub roles: Vec<String>,
+}
+
+impl ColumnAccess {
+    pub fn allows(&self, identity: &Identity) -> bool {
+        identity.roles.iter().any(|r| self.roles.contains(r))
+    }
+}
+
+pub const MASK: &str = "***";
+
+pub fn configured_hash_salt() -> Option<String> {
+    env::var("LUZMO_PLUGIN_HASH_SALT").ok().filter(|s| !s.trim().is_empty())
+}
+
+// Unsalted hashes of a small id space can be reversed by trying every id, so the
+// server does not start without a salt when a column is hashed.
+pub fn check_hash_salt(schema: &Schema, salt: Option<&str>) -> Result<(), String> {
+    let hashed = schema.access.values().any(|a| a.restriction == Restriction::Hashed);
+    if hashed && salt.is_none() {
+        return Err("LUZMO_PLUGIN_HASH_SALT must be set when a column is hashed".to_string());
+    }
+    Ok(())
+}
+
+// Read once. Embedded use without a configured salt (tests, tools) gets a random
+// one per process from the OS generator: hashes are then only stable until a restart.
+fn hash_salt() -> &'static str {
+    static SALT: OnceLock<String> = OnceLock::new();
+    SALT.get_or_init(|| configured_hash_salt().unwrap_or_else(random_salt))
+}
+
+fn random_salt() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("no OS random source for the hash salt");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Identifies the salt without revealing it (masked results differ per salt).
+pub(crate) fn hash_salt_id() -> String {
+    Sha256::digest(hash_salt().as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn hash_value(v: &Value) -> Value {
+    if v.is_null() {
+        return Value::Null;
+    }
+    let digest = Sha256::new()
+        .chain_update(hash_salt().as_bytes())
+        .chain_update(v.to_string().as_bytes())
+        .finalize();
+    let hex: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    Value::String(format!("h:{}", hex))
+}
+
+// The restriction that applies to `col` for this caller, if any.
+pub fn restriction_for(schema: &Schema, col: &str, identity: &Identity) -> Option<Restriction> {
+    schema
+        .access
+        .get(col)
+        .filter(|a| !a.allows(identity))
+        .map(|a| a.restriction)
+}
+
+// The schema as this caller may use it: hidden columns are dropped from the
+// column index, so plans and filters reject them as unknown columns.
+pub fn schema_for(schema: &Schema, identity: &Identity) -> Schema {
+    let mut view = schema.clone();
+    view.col_index
+        .retain(|col, _| restriction_for(schema, col, identity) != Some(Restriction::Hidden));
+    view
+}
+
+// Removes hidden columns from a /datasets entry.
+pub fn strip_hidden_columns(mut dataset: Value, schema: &Schema, identity: &Identity) -> Value {
+    if let Some(Value::Array(cols)) = dataset.get_mut("columns") {
+        cols.retain(|c| {
+            let id = c["id"].as_str().unwrap_or("");
+            restriction_for(schema, id, identity) != Some(Restriction::Hidden)
+        });
+    }
+    dataset
+}
+
+// Column positions the caller may not see at all (for raw mode without columns).
+pub fn hidden_indices(schema: &Schema, identity: &Identity) -> Vec<usize> {
+    schema
+        .col_index
+        .iter()
+        .filter(|(col, _)| restriction_for(schema, col, identity) == Some(Restriction::Hidden))
+        .map(|(_, idx)| *idx)
+        .collect()
+}
+
+// The masked/hashed column positions for one caller, applied a row at a time.
+pub(crate) struct Masking(Vec<(usize, Restriction)>);
+
+impl Masking {
+    pub(crate) fn for_identity(schema: &Schema, identity: &Identity) -> Self {
+        Masking(
+            schema
+                .col_index
+                .iter()
+                .filter_map(|(col, idx)| match restriction_for(schema, col, identity) {
+                    Some(r @ (Restriction::Masked | Restriction::Hashed)) => Some((*idx, r)),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn apply(&self, row: &mut [Value]) {
+        for (idx, restriction) in &self.0 {
+            if let Some(cell) = row.get_mut(*idx) {
+                *cell = match restriction {
+                    Restriction::Hashed => hash_value(cell),
+                    _ if cell.is_null() => Value::Null,
+                    _ => Value::String(MASK.to_string()),
+                };
+            }
+        }
+    }
+}
@@ -1,20 +1,33 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
+use chrono::{Datelike, Duration, NaiveDate};
+use rust_decimal::Decimal;
 use serde_json::{json, Value};
 
+use crate::engine::bins::{all_bins, assign_bin};
+use crate::engine::cancel::{Cancellation, CHECK_EVERY};
+use crate::engine::numeric::to_decimal;
 use crate::engine::plan::{Measure, QueryPlan};
 use crate::errors::PluginError;
+use crate::luzmo::types::BinSpec;
 use crate::utils::sanitize::{normalize_value, sanitize_json_value};
 
 #[derive(Default, Clone)]
 struct AggState {
     count: f64,
     sum: f64,
+    dec_sum: Decimal,
+    // a value or the running sum left Decimal's range; `sum` is used instead
+    dec_overflow: bool,
     min: Option<f64>,
     max: Option<f64>,
 }
 
-fn round2f(x: f64) -> f64 {
+// (group values, sort values, measure states)
+type GroupEntry = (Vec<Value>, Vec<Value>, Vec<AggState>);
+
+pub(crate) fn round2f(x: f64) -> f64 {
     (x * 100.0).round() / 100.0
 }
 
@@ -27,6 +40,33 @@ pub fn bucket_month(v: &Value) -> Value {
     v.clone()
 }
 
+pub fn parse_day(v: &Value) -> Option<NaiveDate> {
+    let s = v.as_str()?;
+    NaiveDate::parse_from_str(s.get(0..10)?, "%Y-%m-%d").ok()
+}
+
+pub fn format_day(d: NaiveDate) -> Value {
+    Value::String(format!("{}T00:00:00.000Z", d.format("%Y-%m-%d")))
+}
+
+// Truncates a date value to the start of its year/quarter/month/week/day bucket.
+pub fn bucket_date(v: &Value, level: &str) -> Value {
+    if level == "month" {
+        return bucket_month(v);
+    }
+    let Some(d) = parse_day(v) else {
+        return v.clone();
+    };
+    let start = match level {
+        "year" => NaiveDate::from_ymd_opt(d.year(), 1, 1),
+        "quarter" => NaiveDate::from_ymd_opt(d.year(), (d.month0() / 3) * 3 + 1, 1),
+        "week" => Some(d - Duration::days(d.weekday().num_days_from_monday() as i64)),
+        "day" => Some(d),
+        _ => return v.clone(),
+    };
+    start.map(format_day).unwrap_or_else(|| v.clone())
+}
+
 fn measure_update(st: &mut AggState, m: &Measure, raw: Value) -> Result<(), PluginError> {
     match m.agg.as_str() {
         "count" => {
@@ -45,6 +85,12 @@ fn measure_update(st: &mut AggState, m: &Measure, raw: Value) -> Result<(), Plug
             if let Some(n) = v.as_f64() {
                 st.count += 1.0;
                 st.sum += n;
+                if m.format.decimal && !st.dec_overflow {
+                    match to_decimal(&v).and_then(|d| st.dec_sum.checked_add(d)) {
+                        Some(sum) => st.dec_sum = sum,
+                        None => st.dec_overflow = true,
+                    }
+                }
                 st.min = Some(st.min.map(|m| m.min(n)).unwrap_or(n));
                 st.max = Some(st.max.map(|m| m.max(n)).unwrap_or(n));
             }
@@ -59,23 +105,96 @@ fn measure_update(st: &mut AggState, m: &Measure, raw: Value) -> Result<(), Plug
 fn measure_finalize(st: &AggState, m: &Measure) -> Value {
     match m.agg.as_str() {
         "count" => json!(st.count as i64),
-        "sum" => json!(round2f(st.sum)),
-        "avg" => json!(if st.count > 0.0 { round2f(st.sum / st.count) } else { 0.0 }),
-        "min" => json!(round2f(st.min.unwrap_or(0.0))),
-        "max" => json!(round2f(st.max.unwrap_or(0.0))),
+        "sum" if m.format.decimal && !st.dec_overflow => m.format.round_decimal(st.dec_sum),
+        "sum" => m.format.round(st.sum),
+        "avg" if st.count == 0.0 => m.format.fractional().round(0.0),
+        "avg" if m.format.decimal && !st.dec_overflow => m
+            .format
+            .fractional()
+            .round_decimal(st.dec_sum / Decimal::from(st.count as i64)),
+        "avg" => m.format.fractional().round(st.sum / st.count),
+        "min" => m.format.round(st.min.unwrap_or(0.0)),
+        "max" => m.format.round(st.max.unwrap_or(0.0)),
         _ => Value::Null,
     }
 }
 
+pub fn cmp_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(an), Value::Number(bn)) => an
+            .as_f64()
+            .unwrap_or(0.0)
+            .partial_cmp(&bn.as_f64().unwrap_or(0.0))
+            .unwrap_or(Ordering::Equal),
+        (Value::String(as_), Value::String(bs)) => as_.cmp(bs),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+// Adds a zero-valued group for every bin missing next to a combination of the
+// other group columns found in the data. `seen` holds the groups from the data
+// only, so the bins filled for one binned column don't multiply another's.
+fn fill_empty_bins(
+    groups: &mut HashMap<String, GroupEntry>,
+    seen: &[(Vec<Value>, Vec<Value>)],
+    gi: usize,
+    col: &str,
+    spec: &BinSpec,
+    n_measures: usize,
+) -> Result<(), PluginError> {
+    let observed: Vec<i64> = seen.iter().filter_map(|(_, s)| s[gi].as_i64()).collect();
+    let bins = all_bins(col, spec, &observed)?;
+
+    let mut others: HashMap<String, (Vec<Value>, Vec<Value>)> = HashMap::new();
+    for (gvals, svals) in seen {
+        let mut rest = gvals.clone();
+        rest[gi] = Value::Null;
+        let key = serde_json::to_string(&rest).unwrap_or_default();
+        others.entry(key).or_insert_with(|| (gvals.clone(), svals.clone()));
+    }
+
+    for (gvals, svals) in others.into_values() {
+        for b in &bins {
+            let mut gv = gvals.clone();
+            let mut sv = svals.clone();
+            gv[gi] = b.label();
+            sv[gi] = b.sort_value();
+
+            let key = serde_json::to_string(&gv).unwrap_or_default();
+            groups
+                .entry(key)
+                .or_insert_with(|| (gv, sv, vec![AggState::default(); n_measures]));
+        }
+    }
+    Ok(())
+}
+
 pub fn execute_aggregation(
     rows: &[Vec<Value>],
     plan: &QueryPlan,
     col_index: &HashMap<String, usize>,
 ) -> Result<Vec<Vec<Value>>, PluginError> {
-    let mut groups: HashMap<String, (Vec<Value>, Vec<AggState>)> = HashMap::new();
+    execute_aggregation_until(rows, plan, col_index, &Cancellation::default())
+}
 
-    for r in rows {
+// execute_aggregation that stops with a Timeout error once `cancel` fires.
+pub fn execute_aggregation_until(
+    rows: &[Vec<Value>],
+    plan: &QueryPlan,
+    col_index: &HashMap<String, usize>,
+    cancel: &Cancellation,
+) -> Result<Vec<Vec<Value>>, PluginError> {
+    let mut groups: HashMap<String, GroupEntry> = HashMap::new();
+
+    for (i, r) in rows.iter().enumerate() {
+        if i.is_multiple_of(CHECK_EVERY) {
+            cancel.check()?;
+        }
         let mut gvals: Vec<Value> = Vec::with_capacity(plan.group_cols.len());
+        let mut svals: Vec<Value> = Vec::with_capacity(plan.group_cols.len());
 
         for g in &plan.group_cols {
             let idx = *col_index.get(&g.id).ok_or_else(|| PluginError::UnknownColumn {
@@ -85,8 +204,13 @@ pub fn execute_aggregation(
             let mut v = r.get(idx).cloned().unwrap_or(Value::Null);
             v = normalize_value(&v);
 
-            if g.id == "date" && g.level.as_deref() == Some("month") {
-                v = bucket_month(&v);
+            if g.id == "date" {
+                if let Some(level) = g.level.as_deref() {
+                    v = bucket_date(&v, level);
+                }
+            }
+            if let Some(d) = &g.drill {
+                v = d.hierarchy.node_at(&v, d.depth);
             }
             if g.col_type.as_deref() == Some("hierarchy") {
                 if let Value::String(_) = v {
@@ -94,17 +218,25 @@ pub fn execute_aggregation(
                 }
             }
 
+            let mut sort = v.clone();
+            if let Some(spec) = &g.bins {
+                let bin = assign_bin(&v, spec);
+                v = bin.as_ref().map(|b| b.label()).unwrap_or(Value::Null);
+                sort = bin.map(|b| b.sort_value()).unwrap_or(Value::Null);
+            }
+
             gvals.push(sanitize_json_value(v));
+            svals.push(sort);
         }
 
         let key = serde_json::to_string(&gvals).unwrap_or_default();
 
         let entry = groups
             .entry(key)
-            .or_insert_with(|| (gvals.clone(), vec![AggState::default(); plan.measures.len()]));
+            .or_insert_with(|| (gvals.clone(), svals, vec![AggState::default(); plan.measures.len()]));
 
         for (mi, m) in plan.measures.iter().enumerate() {
-            let st = &mut entry.1[mi];
+            let st = &mut entry.2[mi];
 
             if m.agg == "count" && m.id == "*" {
                 measure_update(st, m, Value::Null)?;
@@ -120,11 +252,27 @@ pub fn execute_aggregation(
         }
     }
 
-    let mut items: Vec<(String, (Vec<Value>, Vec<AggState>))> = groups.into_iter().collect();
-    items.sort_by(|a, b| a.0.cmp(&b.0));
+    if plan.group_cols.iter().any(|g| g.bins.as_ref().is_some_and(|b| b.include_empty)) {
+        let seen: Vec<(Vec<Value>, Vec<Value>)> = groups.values().map(|e| (e.0.clone(), e.1.clone())).collect();
+        for (gi, g) in plan.group_cols.iter().enumerate() {
+            if let Some(spec) = g.bins.as_ref().filter(|b| b.include_empty) {
+                fill_empty_bins(&mut groups, &seen, gi, &g.id, spec, plan.measures.len())?;
+            }
+        }
+    }
+
+    let mut items: Vec<(String, GroupEntry)> = groups.into_iter().collect();
+    items.sort_by(|a, b| {
+        a.1 .1
+            .iter()
+            .zip(&b.1 .1)
+            .map(|(x, y)| cmp_values(x, y))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or_else(|| a.0.cmp(&b.0))
+    });
 
     let mut out: Vec<Vec<Value>> = Vec::with_capacity(items.len());
-    for (_k, (gvals, mstates)) in items {
+    for (_k, (gvals, _svals, mstates)) in items {
         let mut row = vec![];
         row.extend(gvals);
 
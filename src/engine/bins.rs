@@ -0,0 +1,119 @@
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+use crate::errors::PluginError;
+use crate::luzmo::types::BinSpec;
+use crate::utils::sanitize::normalize_value;
+
+// Numeric binning for group columns: fixed-width bins (origin + n * width)
+// or custom edges. Bins are half-open [lower, upper); with custom edges, values
+// below the first edge fall in "<first" and values from the last edge on in ">=last".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bin {
+    pub index: i64,
+    pub lower: Option<f64>,
+    pub upper: Option<f64>,
+}
+
+// Upper bound on the bins one column may produce, empty ones included.
+pub const MAX_BINS: usize = 1000;
+
+fn too_many_bins(col: &str) -> PluginError {
+    PluginError::InvalidRequest {
+        message: format!("Bins on {} would produce more than {} bins", col, MAX_BINS),
+    }
+}
+
+pub fn validate_bins(col: &str, spec: &BinSpec) -> Result<(), PluginError> {
+    match (&spec.edges, spec.width) {
+        (Some(edges), _) => {
+            if edges.len() < 2 || edges.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(PluginError::InvalidRequest {
+                    message: format!("Bins on {} need at least two ascending edges", col),
+                });
+            }
+            // inner bins plus the two open-ended ones
+            if edges.len() + 1 > MAX_BINS {
+                return Err(too_many_bins(col));
+            }
+        }
+        (None, Some(w)) if w > 0.0 && w.is_finite() => {}
+        _ => {
+            return Err(PluginError::InvalidRequest {
+                message: format!("Bins on {} need a positive width or edges", col),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn fmt_edge(x: f64) -> String {
+    if x.fract() == 0.0 {
+        format!("{}", x as i64)
+    } else {
+        format!("{}", x)
+    }
+}
+
+impl Bin {
+    pub fn label(&self) -> Value {
+        match (self.lower, self.upper) {
+            (Some(lo), Some(hi)) => json!(format!("{}-{}", fmt_edge(lo), fmt_edge(hi))),
+            (None, Some(hi)) => json!(format!("<{}", fmt_edge(hi))),
+            (Some(lo), None) => json!(format!(">={}", fmt_edge(lo))),
+            (None, None) => Value::Null,
+        }
+    }
+
+    pub fn sort_value(&self) -> Value {
+        json!(self.index)
+    }
+}
+
+fn bin_at(spec: &BinSpec, index: i64) -> Bin {
+    if let Some(edges) = &spec.edges {
+        let last = edges.len() as i64 - 1;
+        return Bin {
+            index,
+            lower: if index < 0 { None } else { edges.get(index as usize).copied() },
+            upper: if index >= last { None } else { edges.get((index + 1) as usize).copied() },
+        };
+    }
+    let width = spec.width.unwrap_or(1.0);
+    let lower = spec.origin.unwrap_or(0.0) + index as f64 * width;
+    Bin { index, lower: Some(lower), upper: Some(lower + width) }
+}
+
+pub fn assign_bin(v: &Value, spec: &BinSpec) -> Option<Bin> {
+    let n = normalize_value(v).as_f64()?;
+
+    let index = if let Some(edges) = &spec.edges {
+        // -1 below the first edge, edges.len() - 1 from the last edge on
+        edges.partition_point(|e| *e <= n) as i64 - 1
+    } else {
+        let width = spec.width.unwrap_or(1.0);
+        ((n - spec.origin.unwrap_or(0.0)) / width).floor() as i64
+    };
+
+    Some(bin_at(spec, index))
+}
+
+// The bins a binned column should show when `include_empty` is set: every inner
+// custom bin, or every fixed-width bin between the lowest and highest one observed.
+// Fixed-width ranges depend on the data, so the cap is checked here as well.
+pub fn all_bins(col: &str, spec: &BinSpec, observed: &[i64]) -> Result<Vec<Bin>, PluginError> {
+    let mut indices: BTreeSet<i64> = match &spec.edges {
+        Some(edges) => (0..edges.len() as i64 - 1).collect(),
+        None => match (observed.iter().min(), observed.iter().max()) {
+            (Some(lo), Some(hi)) => {
+                if hi.abs_diff(*lo) >= MAX_BINS as u64 {
+                    return Err(too_many_bins(col));
+                }
+                (*lo..=*hi).collect()
+            }
+            _ => BTreeSet::new(),
+        },
+    };
+    indices.extend(observed);
+    Ok(indices.into_iter().map(|i| bin_at(spec, i)).collect())
+}
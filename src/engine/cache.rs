@@ -0,0 +1,269 @@
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::mem;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::engine::access::hash_salt_id;
+use crate::engine::context::QueryContext;
+use crate::engine::dataset::dataset_version;
+use crate::engine::filters::{normalize_op, resolve_column_id};
+use crate::engine::plan::{col_id, normalize_agg};
+use crate::engine::stats::QueryStats;
+use crate::luzmo::types::{Column, FilterExpr, QueryRequest};
+use crate::utils::ids::random_bits;
+
+pub type Rows = Arc<Vec<Vec<Value>>>;
+
+fn filter_keys(filters: &[FilterExpr]) -> Vec<Value> {
+    filters
+        .iter()
+        .map(|f| {
+            json!({
+                "column": resolve_column_id(f),
+                "op": normalize_op(f.expression.as_deref()),
+                "value": f.value,
+            })
+        })
+        .collect()
+}
+
+fn column_key(c: &Column) -> Value {
+    json!({
+        "column": col_id(c),
+        "aggregation": normalize_agg(c),
+        "level": c.level,
+        "type": c.r#type,
+        "window": c.window,
+        "window_size": c.window_size,
+        "comparison": c.comparison,
+        "comparison_output": c.comparison_output,
+        "bins": c.bins,
+    })
+}
+
+// Canonical form of a query: everything that changes its result, and nothing
+// else (key order, operator and column spelling and the like are normalized).
+// The caller's identity and row-level security filters are part of it because
+// rows and column access differ per user, role and tenant; the hash salt
+// because it changes masked values.
+pub fn cache_key(req: &QueryRequest, ctx: &QueryContext) -> String {
+    let dataset_id = req
+        .dataset_id
+        .as_deref()
+        .or(req.id.as_deref())
+        .unwrap_or("");
+
+    let columns: Option<Vec<Value>> = req.columns.as_ref().map(|cols| cols.iter().map(column_key).collect());
+    let filters = filter_keys(req.filters.as_deref().unwrap_or_default());
+
+    let mut roles = ctx.identity.roles.clone();
+    roles.sort();
+
+    json!({
+        "dataset": dataset_id,
+        "version": dataset_version(dataset_id),
+        "columns": columns,
+        "filters": filters,
+        "row_filters": filter_keys(&ctx.row_filters),
+        "sort": req.options.as_ref().and_then(|o| o.sort.clone()),
+        "limit": req.limit,
+        "tenant": ctx.identity.tenant,
+        "user": ctx.identity.user,
+        "roles": roles,
+        "salt": hash_salt_id(),
+    })
+    .to_string()
+}
+
+// Short hash of a cache key, e.g. for ETags.
+pub fn query_hash(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// New per process: dataset versions restart at 1 and the tenant, policy and
+// credential files are only read at startup, so a tag from before a restart
+// proves nothing.
+fn etag_epoch() -> &'static str {
+    static EPOCH: OnceLock<String> = OnceLock::new();
+    EPOCH.get_or_init(|| format!("{:012x}", random_bits() as u64 & 0xffff_ffff_ffff))
+}
+
+// Same process, same query, same caller, same dataset version: same rows. Weak,
+// since the bytes on the wire differ per content encoding.
+pub fn etag(dataset_id: &str, key: &str) -> String {
+    format!("W/\"{}-{}-{}\"", etag_epoch(), dataset_version(dataset_id), query_hash(key))
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    // 0 disables the cache
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    pub ttl: Duration,
+}
+
+impl CacheConfig {
+    pub fn from_env() -> Self {
+        let num = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default)
+        };
+        CacheConfig {
+            max_entries: num("LUZMO_PLUGIN_CACHE_ENTRIES", 256) as usize,
+            max_bytes: num("LUZMO_PLUGIN_CACHE_MAX_BYTES", 64 * 1024 * 1024) as usize,
+            ttl: Duration::from_secs(num("LUZMO_PLUGIN_CACHE_TTL_SECS", 60)),
+        }
+    }
+}
+
+struct Entry {
+    dataset_id: String,
+    rows: Rows,
+    stats: QueryStats,
+    bytes: usize,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, Entry>,
+    bytes: usize,
+    tick: u64,
+}
+
+// Rough in-memory size of a cached value, without serializing it.
+fn value_size(v: &Value) -> usize {
+    mem::size_of::<Value>()
+        + match v {
+            Value::String(s) => s.len(),
+            Value::Array(items) => items.iter().map(value_size).sum(),
+            Value::Object(map) => map.iter().map(|(k, v)| k.len() + value_size(v)).sum(),
+            _ => 0,
+        }
+}
+
+fn rows_size(rows: &[Vec<Value>]) -> usize {
+    rows.iter()
+        .map(|row| mem::size_of::<Vec<Value>>() + row.iter().map(value_size).sum::<usize>())
+        .sum()
+}
+
+// LRU cache of query results with a TTL, bounded by entry count and by the
+// (estimated) size of the cached rows.
+pub struct ResultCache {
+    config: CacheConfig,
+    state: Mutex<State>,
+}
+
+impl ResultCache {
+    pub fn new(config: CacheConfig) -> Self {
+        ResultCache {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.max_entries > 0
+    }
+
+    pub fn get(&self, key: &str, now: Instant) -> Option<(Rows, QueryStats)> {
+        let mut state = self.state.lock().ok()?;
+        state.tick += 1;
+        let tick = state.tick;
+
+        let expired = now.duration_since(state.entries.get(key)?.inserted_at) >= self.config.ttl;
+        if expired {
+            if let Some(old) = state.entries.remove(key) {
+                state.bytes -= old.bytes;
+            }
+            return None;
+        }
+        let entry = state.entries.get_mut(key)?;
+        entry.last_used = tick;
+        Some((entry.rows.clone(), entry.stats.clone()))
+    }
+
+    pub fn insert(&self, key: String, dataset_id: &str, rows: Rows, stats: QueryStats, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        let bytes = rows_size(&rows) + key.len();
+        if bytes > self.config.max_bytes {
+            return;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.tick += 1;
+        let tick = state.tick;
+
+        if let Some(old) = state.entries.remove(&key) {
+            state.bytes -= old.bytes;
+        }
+        while !state.entries.is_empty()
+            && (state.entries.len() >= self.config.max_entries || state.bytes + bytes > self.config.max_bytes)
+        {
+            let Some(lru) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(old) = state.entries.remove(&lru) {
+                state.bytes -= old.bytes;
+            }
+        }
+
+        state.bytes += bytes;
+        state.entries.insert(
+            key,
+            Entry {
+                dataset_id: dataset_id.to_string(),
+                rows,
+                stats,
+                bytes,
+                inserted_at: now,
+                last_used: tick,
+            },
+        );
+    }
+
+    pub fn invalidate_dataset(&self, dataset_id: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            let State { entries, bytes, .. } = &mut *state;
+            entries.retain(|_, e| {
+                let keep = e.dataset_id != dataset_id;
+                if !keep {
+                    *bytes -= e.bytes;
+                }
+                keep
+            });
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().map(|s| s.entries.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub fn result_cache() -> &'static ResultCache {
+    static CACHE: OnceLock<ResultCache> = OnceLock::new();
+    CACHE.get_or_init(|| ResultCache::new(CacheConfig::from_env()))
+}
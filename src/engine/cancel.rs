@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::errors::PluginError;
+use crate::utils::metrics::metrics;
+
+// Row loops check for cancellation once per this many rows.
+pub const CHECK_EVERY: usize = 1024;
+
+// Deadline and cancel flag of one query. The engine checks it between phases
+// and inside the filter and aggregation loops; clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    deadline: Option<Instant>,
+    timeout: Option<Duration>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    pub fn with_timeout(timeout: Option<Duration>) -> Self {
+        Cancellation {
+            deadline: timeout.map(|t| Instant::now() + t),
+            timeout,
+            ..Default::default()
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub fn check(&self) -> Result<(), PluginError> {
+        if self.is_cancelled() {
+            return Err(PluginError::Timeout {
+                message: "Query cancelled".to_string(),
+            });
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(self.expired()),
+            _ => Ok(()),
+        }
+    }
+
+    // The error for a query that ran (or waited) past its deadline.
+    pub fn expired(&self) -> PluginError {
+        PluginError::Timeout {
+            message: format!(
+                "Query exceeded the timeout of {} ms",
+                self.timeout.unwrap_or_default().as_millis()
+            ),
+        }
+    }
+}
+
+// Cancels the query when dropped, e.g. when actix drops the handler future
+// because the client went away. Disarm it once the query has finished.
+pub struct CancelOnDrop {
+    cancel: Option<Cancellation>,
+    dataset_id: String,
+}
+
+impl CancelOnDrop {
+    pub fn new(cancel: &Cancellation, dataset_id: &str) -> Self {
+        CancelOnDrop {
+            cancel: Some(cancel.clone()),
+            dataset_id: dataset_id.to_string(),
+        }
+    }
+
+    pub fn disarm(mut self) {
+        self.cancel = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(cancel) = &self.cancel {
+            cancel.cancel();
+            metrics().record_cancelled(&self.dataset_id);
+        }
+    }
+}
+
+fn parse_timeouts(raw: &str) -> HashMap<String, u64> {
+    raw.split(',')
+        .filter_map(|pair| {
+            let (dataset, ms) = pair.split_once('=')?;
+            Some((dataset.trim().to_string(), ms.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// LUZMO_PLUGIN_DATASET_TIMEOUTS ("demo=5000,sales=60000") overrides the global
+// LUZMO_PLUGIN_QUERY_TIMEOUT_MS (default 30000). 0 means no timeout.
+pub fn timeout_for(dataset_id: &str) -> Option<Duration> {
+    let per_dataset = env::var("LUZMO_PLUGIN_DATASET_TIMEOUTS")
+        .ok()
+        .and_then(|raw| parse_timeouts(&raw).get(dataset_id).copied());
+    let ms = per_dataset.unwrap_or_else(|| {
+        env::var("LUZMO_PLUGIN_QUERY_TIMEOUT_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(30_000)
+    });
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Months, NaiveDate};
+use serde_json::{json, Value};
+
+use crate::engine::aggregation::{bucket_date, execute_aggregation_until, format_day, parse_day, round2f};
+use crate::engine::cancel::{Cancellation, CHECK_EVERY};
+use crate::engine::dataset::Schema;
+use crate::engine::filters::{apply_filters_until, resolve_column_id};
+use crate::engine::numeric::NumberFormat;
+use crate::engine::plan::{Measure, QueryPlan};
+use crate::errors::PluginError;
+use crate::luzmo::types::FilterExpr;
+
+// Period-over-period: each date bucket is compared with the bucket one period
+// (or one year) earlier. The comparison buckets are aggregated without the date
+// filters of the request, so the first requested period still finds its
+// predecessor. `data` is the caller's view, row-level security is already applied.
+pub const COMPARISON_PERIODS: [&str; 2] = ["previous_period", "previous_year"];
+pub const COMPARISON_OUTPUTS: [&str; 3] = ["value", "absolute", "relative"];
+
+fn date_group_index(plan: &QueryPlan) -> Option<usize> {
+    plan.group_cols.iter().position(|g| g.id == "date")
+}
+
+pub fn validate_comparison(m: &Measure, plan: &QueryPlan) -> Result<(), PluginError> {
+    let Some(period) = m.comparison.as_deref() else {
+        return Ok(());
+    };
+    if !COMPARISON_PERIODS.contains(&period) {
+        return Err(PluginError::InvalidRequest {
+            message: format!("Unsupported comparison: {}", period),
+        });
+    }
+    let output = m.comparison_output.as_deref().unwrap_or("absolute");
+    if !COMPARISON_OUTPUTS.contains(&output) {
+        return Err(PluginError::InvalidRequest {
+            message: format!("Unsupported comparison_output: {}", output),
+        });
+    }
+    if date_group_index(plan).is_none() {
+        return Err(PluginError::InvalidRequest {
+            message: format!("Comparison on {} requires grouping by date", m.id),
+        });
+    }
+    Ok(())
+}
+
+fn shift_back(d: NaiveDate, level: &str, period: &str) -> Option<NaiveDate> {
+    if period == "previous_year" {
+        return d.checked_sub_months(Months::new(12));
+    }
+    match level {
+        "year" => d.checked_sub_months(Months::new(12)),
+        "quarter" => d.checked_sub_months(Months::new(3)),
+        "month" => d.checked_sub_months(Months::new(1)),
+        "week" => d.checked_sub_signed(Duration::days(7)),
+        _ => d.checked_sub_signed(Duration::days(1)),
+    }
+}
+
+fn compare(output: &str, fmt: NumberFormat, current: Option<f64>, previous: Option<f64>) -> Value {
+    match (output, current, previous) {
+        ("value", _, Some(p)) => fmt.round(p),
+        ("absolute", Some(c), Some(p)) => fmt.round(c - p),
+        ("relative", Some(c), Some(p)) if p != 0.0 => json!(round2f((c - p) / p.abs() * 100.0)),
+        _ => Value::Null,
+    }
+}
+
+// Fills the comparison measures of aggregated rows from the previous period.
+// Stops with a Timeout error once `cancel` fires; it scans `data` a second
+// time, so it checks as often as the first pass.
+pub fn apply_comparisons_until(
+    rows: &mut [Vec<Value>],
+    plan: &QueryPlan,
+    data: &[Vec<Value>],
+    filters: Option<&[FilterExpr]>,
+    schema: &Schema,
+    cancel: &Cancellation,
+) -> Result<(), PluginError> {
+    if plan.measures.iter().all(|m| m.comparison.is_none()) {
+        return Ok(());
+    }
+    let Some(di) = date_group_index(plan) else {
+        return Ok(());
+    };
+    let date_col = &plan.group_cols[di];
+    let level = date_col.level.as_deref().unwrap_or("day");
+
+    let outside_window: Vec<FilterExpr> = filters
+        .unwrap_or_default()
+        .iter()
+        .filter(|f| resolve_column_id(f).as_deref() != Some(date_col.id.as_str()))
+        .cloned()
+        .collect();
+
+    let base = apply_filters_until(data, Some(outside_window), schema, cancel)?;
+    let n_groups = plan.group_cols.len();
+
+    let by_key: HashMap<String, Vec<Value>> = execute_aggregation_until(&base, plan, &schema.col_index, cancel)?
+        .into_iter()
+        .map(|r| (serde_json::to_string(&r[..n_groups]).unwrap_or_default(), r))
+        .collect();
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        if i.is_multiple_of(CHECK_EVERY) {
+            cancel.check()?;
+        }
+        for (mi, m) in plan.measures.iter().enumerate() {
+            let Some(period) = m.comparison.as_deref() else {
+                continue;
+            };
+            let col = n_groups + mi;
+
+            let previous = parse_day(&row[di])
+                .and_then(|d| shift_back(d, level, period))
+                // a year back from a Monday is not a Monday: snap to the bucket
+                .map(|d| bucket_date(&format_day(d), level))
+                .and_then(|bucket| {
+                    let mut key = row[..n_groups].to_vec();
+                    key[di] = bucket;
+                    by_key.get(&serde_json::to_string(&key).unwrap_or_default())
+                })
+                .and_then(|r| r[col].as_f64());
+
+            let output = m.comparison_output.as_deref().unwrap_or("absolute");
+            row[col] = compare(output, m.format, row[col].as_f64(), previous);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use crate::engine::cancel::Cancellation;
+use crate::luzmo::types::FilterExpr;
+use crate::utils::credentials::Identity;
+
+// Per-request state the engine needs besides the QueryRequest itself.
+#[derive(Debug, Clone, Default)]
+pub struct QueryContext {
+    pub identity: Identity,
+    // Row-level security filters; applied to the dataset before the request
+    // filters run, so comparison windows cannot escape them either.
+    pub row_filters: Vec<FilterExpr>,
+    // Only used to correlate log lines.
+    pub request_id: String,
+    pub cancel: Cancellation,
+}
+
+impl QueryContext {
+    pub fn new(identity: Identity) -> Self {
+        QueryContext {
+            identity,
+            ..Default::default()
+        }
+    }
+}
@@ -1,12 +1,100 @@
 use chrono::{Duration, NaiveDate};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::engine::access::{ColumnAccess, Restriction};
+use crate::engine::cache::result_cache;
+use crate::engine::hierarchy::Hierarchy;
+use crate::engine::numeric::NumberFormat;
+
+// Everything the engine needs to know about a dataset besides its rows.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    pub col_index: HashMap<String, usize>,
+    pub hierarchies: HashMap<String, Hierarchy>,
+    pub formats: HashMap<String, NumberFormat>,
+    pub access: HashMap<String, ColumnAccess>,
+}
+
+impl Schema {
+    pub fn format(&self, col: &str) -> NumberFormat {
+        self.formats.get(col).copied().unwrap_or_default()
+    }
+}
+
+// The datasets this plugin serves; anything else is DatasetNotFound.
+pub fn is_known_dataset(dataset_id: &str) -> bool {
+    dataset_id == "demo"
+}
+
+pub fn demo_schema() -> Schema {
+    Schema {
+        col_index: col_index_map(),
+        hierarchies: hierarchies(),
+        formats: number_formats(),
+        access: column_access(),
+    }
+}
 
 pub fn col_index_map() -> HashMap<String, usize> {
     let mut map = HashMap::new();
     map.insert("category".to_string(), 0);
     map.insert("date".to_string(), 1);
     map.insert("value".to_string(), 2);
+    map.insert("customer".to_string(), 3);
+    map
+}
+
+// Customer ids are only visible to admins; everyone else sees a stable hash.
+pub fn column_access() -> HashMap<String, ColumnAccess> {
+    let mut map = HashMap::new();
+    map.insert(
+        "customer".to_string(),
+        ColumnAccess {
+            restriction: Restriction::Hashed,
+            roles: vec!["admin".to_string()],
+        },
+    );
+    map
+}
+
+// Category is the leaf of Division > Segment > Category.
+pub fn hierarchies() -> HashMap<String, Hierarchy> {
+    let category = Hierarchy::from_parents(
+        &["division", "segment", "category"],
+        &[
+            ("Consumer", None),
+            ("Business", None),
+            ("Retail", Some("Consumer")),
+            ("Online", Some("Consumer")),
+            ("Wholesale", Some("Business")),
+            ("Services", Some("Business")),
+            ("A", Some("Retail")),
+            ("B", Some("Retail")),
+            ("C", Some("Online")),
+            ("D", Some("Wholesale")),
+            ("E", Some("Services")),
+            ("F", Some("Services")),
+        ],
+    );
+
+    let mut map = HashMap::new();
+    map.insert("category".to_string(), category);
+    map
+}
+
+// value is a currency amount: summed exactly, shown with 2 decimals.
+pub fn number_formats() -> HashMap<String, NumberFormat> {
+    let mut map = HashMap::new();
+    map.insert(
+        "value".to_string(),
+        NumberFormat {
+            precision: 2,
+            decimal: true,
+            integer: false,
+        },
+    );
     map
 }
 
@@ -33,14 +121,51 @@ pub fn generate_data() -> Vec<Vec<Value>> {
                 let value = (base + seasonal + noise).max(0.0);
                 let rounded = (value * 100.0).round() / 100.0;
 
+                let customer = format!("CUST-{:04}", (rows.len() * 7919) % 500);
+
                 rows.push(vec![
                 json!(category.to_string()),
                 json!(date_str.clone()),
                 json!(rounded),
+                json!(customer),
             ]);
             }
         }
     }
 
     rows
-}
\ No newline at end of file
+}
+
+// The demo rows are deterministic: generated once and shared by every query,
+// which reads them without copying.
+pub fn demo_rows() -> Arc<Vec<Vec<Value>>> {
+    static ROWS: OnceLock<Arc<Vec<Vec<Value>>>> = OnceLock::new();
+    ROWS.get_or_init(|| Arc::new(generate_data())).clone()
+}
+
+fn versions() -> &'static Mutex<HashMap<String, u64>> {
+    static VERSIONS: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+    VERSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Changes whenever the dataset's data is reloaded; cached results are tied to it.
+pub fn dataset_version(dataset_id: &str) -> u64 {
+    versions()
+        .lock()
+        .map(|v| v.get(dataset_id).copied().unwrap_or(1))
+        .unwrap_or(1)
+}
+
+// Call after (re)loading a dataset's data: bumps its version and drops its cached results.
+pub fn mark_dataset_reloaded(dataset_id: &str) -> u64 {
+    let version = match versions().lock() {
+        Ok(mut v) => {
+            let version = v.entry(dataset_id.to_string()).or_insert(1);
+            *version += 1;
+            *version
+        }
+        Err(_) => 1,
+    };
+    result_cache().invalidate_dataset(dataset_id);
+    version
+}
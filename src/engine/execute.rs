@@ -1,53 +1,36 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 use serde_json::Value;
 
-use crate::engine::aggregation::{bucket_month, execute_aggregation};
-use crate::engine::dataset::{col_index_map, generate_data};
-use crate::engine::filters::apply_filters;
+use crate::engine::access::{hidden_indices, schema_for, Masking};
+use crate::engine::cache::{cache_key, result_cache, Rows};
+use crate::engine::aggregation::execute_aggregation_until;
+use crate::engine::comparison::{apply_comparisons_until, validate_comparison};
+use crate::engine::dataset::{demo_rows, demo_schema, is_known_dataset};
+use crate::engine::filters::{compile_filters, compile_policy_filters, filter_rows};
 use crate::engine::plan::{build_plan, QueryPlan};
+use crate::engine::rows::{FilteredRows, RawRows};
+use crate::engine::stats::{elapsed_ms, PhaseTimings, QueryStats};
+use crate::engine::window::apply_windows_until;
 use crate::errors::PluginError;
-use crate::luzmo::types::{Column, QueryRequest};
-use crate::utils::sanitize::{normalize_value, sanitize_json_value};
-// Main query execution logic
-fn col_id(c: &Column) -> String {
-    c.column_id
-        .clone()
-        .or_else(|| if c.id.is_empty() { None } else { Some(c.id.clone()) })
-        .unwrap_or_default()
+use crate::engine::context::QueryContext;
+use crate::luzmo::types::{FilterExpr, QueryRequest};
+// Main query execution logic; bypasses the result cache (see run_or_stream).
+pub fn run(req: &QueryRequest, ctx: &QueryContext) -> Result<(Vec<Vec<Value>>, QueryStats), PluginError> {
+    let mut stats = QueryStats::default();
+    let rows = match execute_lazy(req, ctx, &mut stats)? {
+        QueryOutput::Raw(rows) => collect_raw(rows, &mut stats)?,
+        QueryOutput::Aggregated(out) => out,
+    };
+    stats.rows_returned = rows.len();
+    Ok((rows, stats))
 }
 
-pub fn run(req: &QueryRequest) -> Result<Vec<Vec<Value>>, PluginError> {
-    execute_query(req)
-}
-
-pub fn execute_query(req: &QueryRequest) -> Result<Vec<Vec<Value>>, PluginError> {
-    let dataset_id = req
-        .dataset_id
-        .as_deref()
-        .or(req.id.as_deref())
-        .unwrap_or("");
-
-    if dataset_id != "demo" {
-        return Err(PluginError::DatasetNotFound {
-            message: format!("Unknown dataset_id: {}", dataset_id),
-        });
-    }
-
-    let data = generate_data();
-    let col_index: HashMap<String, usize> = col_index_map();
-
-    eprintln!(
-        "DEBUG: Request - id: {:?}, dataset_id: {:?}, columns: {:?}",
-        req.id, req.dataset_id, req.columns
-    );
-
-    let filtered = apply_filters(&data, req.filters.clone(), &col_index)?;
-    eprintln!("DEBUG: Filtered rows: {}", filtered.len());
-
-    let plan: QueryPlan = build_plan(req, col_index.clone())?;
-
+pub(crate) fn validate_measures(plan: &QueryPlan) -> Result<(), PluginError> {
     let allowed = ["sum", "count", "avg", "min", "max"];
     for m in &plan.measures {
+        validate_comparison(m, plan)?;
         if m.id == "*" && m.agg == "count" {
             continue;
         }
@@ -57,56 +40,171 @@ pub fn execute_query(req: &QueryRequest) -> Result<Vec<Vec<Value>>, PluginError>
             });
         }
     }
+    Ok(())
+}
+
+// Request filters plus the caller's row-level security filters.
+pub(crate) fn effective_filters(req: &QueryRequest, ctx: &QueryContext) -> Option<Vec<FilterExpr>> {
+    if ctx.row_filters.is_empty() {
+        return req.filters.clone();
+    }
+    let mut all = req.filters.clone().unwrap_or_default();
+    all.extend(ctx.row_filters.iter().cloned());
+    Some(all)
+}
 
-    // geen columns: raw
-    if plan.requested_cols.is_empty() {
-        let mut out = vec![];
-        for mut r in filtered {
-            for cell in r.iter_mut() {
-                *cell = sanitize_json_value(normalize_value(cell));
-            }
-            out.push(r);
-        }
-        if let Some(l) = req.limit {
-            out.truncate(l);
-        }
-        return Ok(out);
+pub enum QueryResult {
+    Rows(Rows),
+    Stream(RawRows),
+}
+
+// The result cache first; otherwise the query runs, and raw results of at least
+// `stream_min_rows` rows are handed back unmaterialized for streaming. Those
+// are never cached.
+pub fn run_or_stream(
+    req: &QueryRequest,
+    ctx: &QueryContext,
+    stream_min_rows: usize,
+) -> Result<(QueryResult, QueryStats), PluginError> {
+    let cache = result_cache();
+    let key = cache.is_enabled().then(|| cache_key(req, ctx));
+    if let Some((rows, mut stats)) = key.as_deref().and_then(|k| cache.get(k, Instant::now())) {
+        // de timings horen bij de run die de cache vulde, niet bij deze
+        stats.cache_hit = true;
+        stats.timings = PhaseTimings::default();
+        return Ok((QueryResult::Rows(rows), stats));
     }
 
-    // raw mode
-    if !plan.has_agg {
-        let mut out: Vec<Vec<Value>> = Vec::with_capacity(filtered.len());
+    let mut stats = QueryStats::default();
+    let rows = match execute_lazy(req, ctx, &mut stats)? {
+        QueryOutput::Raw(rows) if rows.len() >= stream_min_rows => {
+            stats.rows_returned = rows.len();
+            return Ok((QueryResult::Stream(rows), stats));
+        }
+        QueryOutput::Raw(rows) => Arc::new(collect_raw(rows, &mut stats)?),
+        QueryOutput::Aggregated(out) => Arc::new(out),
+    };
+    stats.rows_returned = rows.len();
+
+    if let Some(key) = key {
+        let dataset_id = req.dataset_id.as_deref().or(req.id.as_deref()).unwrap_or("");
+        cache.insert(key, dataset_id, rows.clone(), stats.clone(), Instant::now());
+    }
+    Ok((QueryResult::Rows(rows), stats))
+}
 
-        for r in filtered {
-            let mut row_out = Vec::with_capacity(plan.requested_cols.len());
+fn collect_raw(rows: RawRows, stats: &mut QueryStats) -> Result<Vec<Vec<Value>>, PluginError> {
+    let phase = Instant::now();
+    let out = rows.collect::<Result<_, _>>()?;
+    stats.timings.aggregate_ms = elapsed_ms(phase);
+    Ok(out)
+}
 
-            for c in &plan.requested_cols {
-                let cid = col_id(c);
-                let idx = *col_index.get(&cid).ok_or_else(|| PluginError::UnknownColumn {
-                    message: format!("Unknown column in request: {}", cid),
-                })?;
+// Raw results are left as a lazy iterator, so the caller can stream them.
+enum QueryOutput {
+    Raw(RawRows),
+    Aggregated(Vec<Vec<Value>>),
+}
 
-                let mut v = r.get(idx).cloned().unwrap_or(Value::Null);
-                v = normalize_value(&v);
+fn execute_lazy(
+    req: &QueryRequest,
+    ctx: &QueryContext,
+    stats: &mut QueryStats,
+) -> Result<QueryOutput, PluginError> {
+    let dataset_id = req
+        .dataset_id
+        .as_deref()
+        .or(req.id.as_deref())
+        .unwrap_or("");
 
-                if cid == "date" && c.level.as_deref() == Some("month") {
-                    v = bucket_month(&v);
-                }
+    if !is_known_dataset(dataset_id) {
+        return Err(PluginError::DatasetNotFound {
+            message: format!("Unknown dataset_id: {}", dataset_id),
+        });
+    }
 
-                row_out.push(sanitize_json_value(v));
-            }
+    let full_schema = demo_schema();
+
+    // The caller's view of the dataset: row-level security on the real values,
+    // then masked columns, then hidden columns dropped from the schema.
+    ctx.cancel.check()?;
+    let phase = Instant::now();
+    let data = demo_rows();
+    stats.rows_scanned = data.len();
+    let row_filters = compile_policy_filters(&ctx.row_filters, &full_schema)?;
+    let masking = Masking::for_identity(&full_schema, &ctx.identity);
+    let schema = schema_for(&full_schema, &ctx.identity);
+    let col_index: &HashMap<String, usize> = &schema.col_index;
+
+    log::debug!(
+        request_id = ctx.request_id.as_str(),
+        dataset_id = req.dataset_id.as_deref().or(req.id.as_deref()).unwrap_or(""),
+        user = ctx.identity.user.as_str(),
+        columns = req.columns.as_ref().map_or(0, Vec::len);
+        "query received"
+    );
 
-            out.push(row_out);
+    let filters = compile_filters(req.filters.as_deref().unwrap_or_default(), &schema)?;
+    stats.timings.filter_ms = elapsed_ms(phase);
+
+    let phase = Instant::now();
+    let mut plan: QueryPlan = build_plan(req, &schema)?;
+    plan.filters = effective_filters(req, ctx);
+
+    validate_measures(&plan)?;
+
+    stats.timings.plan_ms = elapsed_ms(phase);
+
+    // raw: de rijen worden pas gefilterd terwijl ze gelezen worden
+    if plan.requested_cols.is_empty() || !plan.has_agg {
+        let phase = Instant::now();
+        let mut rows = FilteredRows::new(data, row_filters, masking, filters, ctx.cancel.clone());
+        let matching = rows.count()?;
+        stats.rows_filtered = matching;
+        stats.timings.filter_ms += elapsed_ms(phase);
+        log::debug!(
+            request_id = ctx.request_id.as_str(),
+            rows_in = stats.rows_scanned,
+            rows_filtered = matching;
+            "filters applied"
+        );
+
+        // geen columns: raw
+        if plan.requested_cols.is_empty() {
+            let hidden = hidden_indices(&full_schema, &ctx.identity);
+            return Ok(QueryOutput::Raw(RawRows::all(rows, matching, hidden, req.limit)));
         }
 
-        if let Some(l) = req.limit {
-            out.truncate(l);
-        }
-        return Ok(out);
+        // raw mode
+        return Ok(QueryOutput::Raw(RawRows::columns(
+            rows,
+            matching,
+            &plan.requested_cols,
+            col_index,
+            &schema.hierarchies,
+            req.limit,
+        )?));
     }
 
+    let phase = Instant::now();
+    let data = FilteredRows::new(data, row_filters, masking, vec![], ctx.cancel.clone()).collect_rows()?;
+    let filtered = filter_rows(&data, &filters, &ctx.cancel)?;
+    stats.rows_filtered = filtered.len();
+    stats.timings.filter_ms += elapsed_ms(phase);
+    log::debug!(
+        request_id = ctx.request_id.as_str(),
+        rows_in = data.len(),
+        rows_filtered = filtered.len();
+        "filters applied"
+    );
+
+    let phase = Instant::now();
+
     // agg mode
-    let mut out = execute_aggregation(&filtered, &plan, &col_index)?;
+    let mut out = execute_aggregation_until(&filtered, &plan, col_index, &ctx.cancel)?;
+    stats.groups = out.len();
+    apply_comparisons_until(&mut out, &plan, &data, req.filters.as_deref(), &schema, &ctx.cancel)?;
+    apply_windows_until(&mut out, &plan, &ctx.cancel)?;
 
     for row in out.iter_mut() {
         for cell in row.iter_mut() {
@@ -121,6 +219,7 @@ pub fn execute_query(req: &QueryRequest) -> Result<Vec<Vec<Value>>, PluginError>
     if let Some(l) = req.limit {
         out.truncate(l);
     }
+    stats.timings.aggregate_ms = elapsed_ms(phase);
 
-    Ok(out)
+    Ok(QueryOutput::Aggregated(out))
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use serde_json::{json, Value};
+
+use crate::engine::access::schema_for;
+use crate::engine::context::QueryContext;
+use crate::engine::dataset::demo_schema;
+use crate::engine::execute::{effective_filters, validate_measures};
+use crate::engine::filters::{normalize_op, resolve_column_id};
+use crate::engine::plan::{build_plan, GroupCol, Measure};
+use crate::errors::PluginError;
+use crate::luzmo::types::QueryRequest;
+
+fn group_col(g: &GroupCol) -> Value {
+    json!({
+        "id": g.id,
+        "level": g.level,
+        "type": g.col_type,
+        "bins": g.bins,
+        "drill": g.drill.as_ref().map(|d| json!({
+            "depth": d.depth,
+            "level": d.depth.checked_sub(1).and_then(|i| d.hierarchy.levels.get(i)),
+        })),
+    })
+}
+
+fn measure(m: &Measure) -> Value {
+    json!({
+        "id": m.id,
+        "aggregation": m.agg,
+        "type": m.col_type,
+        "window": m.window,
+        "window_size": m.window_size,
+        "comparison": m.comparison,
+        "comparison_output": m.comparison_output,
+        "precision": m.format.precision,
+    })
+}
+
+// The plan a query resolves to, without running it. There is no SQL backend
+// yet: everything runs in memory and nothing is pushed down.
+pub fn explain(req: &QueryRequest, ctx: &QueryContext) -> Result<Value, PluginError> {
+    let schema = schema_for(&demo_schema(), &ctx.identity);
+    let mut plan = build_plan(req, &schema)?;
+    plan.filters = effective_filters(req, ctx);
+    validate_measures(&plan)?;
+
+    let mode = if plan.requested_cols.is_empty() {
+        "raw_all"
+    } else if plan.has_agg {
+        "aggregate"
+    } else {
+        "raw"
+    };
+    // alleen de eigen filters tonen, de RLS-filters enkel tellen
+    let filters: Vec<Value> = req
+        .filters
+        .iter()
+        .flatten()
+        .map(|f| {
+            json!({
+                "column_id": resolve_column_id(f),
+                "operator": normalize_op(f.expression.as_deref()),
+                "value": f.value,
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "dataset_id": plan.dataset_id,
+        "backend": "memory",
+        "pushdown": false,
+        "sql": null,
+        "mode": mode,
+        "group_cols": plan.group_cols.iter().map(group_col).collect::<Vec<_>>(),
+        "measures": plan.measures.iter().map(measure).collect::<Vec<_>>(),
+        "filters": filters,
+        "row_security_filters": ctx.row_filters.len(),
+        "sort": req.options.as_ref().and_then(|o| o.sort.clone()),
+        "limit": plan.limit,
+    }))
+}
@@ -1,12 +1,14 @@
 use serde_json::Value;
-use std::collections::HashMap;
 
+use crate::engine::cancel::{Cancellation, CHECK_EVERY};
+use crate::engine::dataset::Schema;
+use crate::engine::hierarchy::Hierarchy;
 use crate::errors::PluginError;
 use crate::luzmo::types::FilterExpr;
 use crate::utils::sanitize::normalize_value;
 
 // Filter application logic
-fn resolve_column_id(f: &FilterExpr) -> Option<String> {
+pub(crate) fn resolve_column_id(f: &FilterExpr) -> Option<String> {
     f.column_id.clone().or_else(|| f.id.clone())
 }
 
@@ -42,7 +44,7 @@ fn cmp_le(a: &Value, b: &Value) -> bool {
     }
 }
 
-fn normalize_op(op: Option<&str>) -> Option<&str> {
+pub(crate) fn normalize_op(op: Option<&str>) -> Option<&str> {
     match op?.trim() {
         ">" | "greater_than" => Some(">"),
         ">=" | "greater_than_or_equal" => Some(">="),
@@ -58,143 +60,188 @@ fn normalize_op(op: Option<&str>) -> Option<&str> {
     }
 }
 
-pub fn apply_filters(
-    rows: &[Vec<Value>],
-    filters: Option<Vec<FilterExpr>>,
-    col_index: &HashMap<String, usize>,
-) -> Result<Vec<Vec<Value>>, PluginError> {
-    let Some(filters) = filters else {
-        return Ok(rows.to_vec());
-    };
-    if filters.is_empty() {
-        return Ok(rows.to_vec());
+// The values an equality-style filter is matched against: the cell itself, or
+// every node on its path for hierarchy columns.
+fn match_values(row: &[Value], idx: usize, hierarchy: Option<&Hierarchy>) -> Option<Vec<Value>> {
+    let v = normalize_value(row.get(idx)?);
+    Some(match hierarchy {
+        Some(h) => h.path_values(&v),
+        None => vec![v],
+    })
+}
+
+// One request filter, resolved against the schema once and then tested row by
+// row, so rows can be filtered lazily (see engine::rows) as well as in bulk.
+pub(crate) struct RowFilter {
+    idx: usize,
+    hierarchy: Option<Hierarchy>,
+    test: Test,
+}
+
+enum Test {
+    NotNull,
+    In(Vec<Value>),
+    Eq(Value),
+    Ne(Value),
+    Ge(Value),
+    Gt(Value),
+    Le(Value),
+    Lt(Value),
+    Contains(String),
+    Any,
+}
+
+impl RowFilter {
+    pub(crate) fn matches(&self, row: &[Value]) -> bool {
+        let (idx, hierarchy) = (self.idx, self.hierarchy.as_ref());
+        match &self.test {
+            Test::NotNull => row.get(idx).map(|v| !v.is_null()).unwrap_or(false),
+            Test::In(vals) => match_values(row, idx, hierarchy)
+                .map(|vs| vs.iter().any(|v| vals.contains(v)))
+                .unwrap_or(false),
+            Test::Eq(val) => match_values(row, idx, hierarchy)
+                .map(|vs| vs.contains(val))
+                .unwrap_or(false),
+            Test::Ne(val) => match_values(row, idx, hierarchy)
+                .map(|vs| !vs.contains(val))
+                .unwrap_or(false),
+            Test::Ge(val) => row.get(idx).is_some_and(|v| cmp_ge(v, val)),
+            Test::Gt(val) => row.get(idx).is_some_and(|v| cmp_gt(v, val)),
+            Test::Le(val) => row.get(idx).is_some_and(|v| cmp_le(v, val)),
+            Test::Lt(val) => row.get(idx).is_some_and(|v| cmp_lt(v, val)),
+            Test::Contains(needle) => match_values(row, idx, hierarchy)
+                .map(|vs| {
+                    vs.iter()
+                        .any(|v| v.as_str().unwrap_or("").to_lowercase().contains(needle))
+                })
+                .unwrap_or(false),
+            Test::Any => true,
+        }
     }
+}
 
-    let mut result: Vec<Vec<Value>> = rows.to_vec();
+pub(crate) fn matches_all(filters: &[RowFilter], row: &[Value]) -> bool {
+    filters.iter().all(|f| f.matches(row))
+}
 
-    for f in filters {
-        let col = resolve_column_id(&f)
-            .filter(|s| !s.trim().is_empty())
-            .ok_or_else(|| PluginError::InvalidRequest {
-                message: "Filter is missing column_id/id".to_string(),
-            })?;
+// Resolves and validates every filter; unknown columns and operators fail here,
+// before any row is read.
+pub(crate) fn compile_filters(filters: &[FilterExpr], schema: &Schema) -> Result<Vec<RowFilter>, PluginError> {
+    filters.iter().map(|f| compile_filter(f, schema)).collect()
+}
 
-        let expr = normalize_op(f.expression.as_deref());
+// compile_filters for row-level security: an 'in' without a value would let
+// every row through, so it is an error here rather than skipped.
+pub(crate) fn compile_policy_filters(filters: &[FilterExpr], schema: &Schema) -> Result<Vec<RowFilter>, PluginError> {
+    if let Some(f) = filters
+        .iter()
+        .find(|f| f.value.is_none() && normalize_op(f.expression.as_deref()) == Some("in"))
+    {
+        return Err(PluginError::InvalidRequest {
+            message: format!("Policy filter 'in' on {} needs a value", resolve_column_id(f).unwrap_or_default()),
+        });
+    }
+    compile_filters(filters, schema)
+}
 
-        let idx = match col_index.get(&col) {
-            Some(&i) => i,
-            None => {
-                return Err(PluginError::UnknownColumn {
-                    message: format!("Unknown column in filter: {}", col),
-                })
-            }
-        };
-
-        let raw_val = f.value.as_ref().map(normalize_value);
-
-        result = match expr {
-            Some("is not null") => result
-                .into_iter()
-                .filter(|row| row.get(idx).map(|v| !v.is_null()).unwrap_or(false))
-                .collect(),
-
-            Some("in") => {
-                let vals = match f.value {
-                    Some(Value::Array(v)) => v,
-                    Some(v) => vec![v],
-                    None => continue,
-                };
-                let vals_norm: Vec<Value> = vals.into_iter().map(|x| normalize_value(&x)).collect();
-
-                result
-                    .into_iter()
-                    .filter(|row| {
-                        row.get(idx)
-                            .map(|v| {
-                                let v = normalize_value(v);
-                                vals_norm.contains(&v)
-                            })
-                            .unwrap_or(false)
-                    })
-                    .collect()
-            }
-
-            Some("=") | Some("==") => {
-                let cmp_val = raw_val.clone().unwrap_or(Value::Null);
-                result
-                    .into_iter()
-                    .filter(|row| row.get(idx).map(|v| normalize_value(v) == cmp_val).unwrap_or(false))
-                    .collect()
-            }
-
-            Some("!=") | Some("!==") => {
-                let cmp_val = raw_val.clone().unwrap_or(Value::Null);
-                result
-                    .into_iter()
-                    .filter(|row| row.get(idx).map(|v| normalize_value(v) != cmp_val).unwrap_or(false))
-                    .collect()
-            }
-
-            Some(">=") => {
-                let cmp_val = raw_val.clone().unwrap_or(Value::Null);
-                result
-                    .into_iter()
-                    .filter(|row| row.get(idx).map_or(false, |v| cmp_ge(v, &cmp_val)))
-                    .collect()
-            }
-
-            Some(">") => {
-                let cmp_val = raw_val.clone().unwrap_or(Value::Null);
-                result
-                    .into_iter()
-                    .filter(|row| row.get(idx).map_or(false, |v| cmp_gt(v, &cmp_val)))
-                    .collect()
-            }
-
-            Some("<=") => {
-                let cmp_val = raw_val.clone().unwrap_or(Value::Null);
-                result
-                    .into_iter()
-                    .filter(|row| row.get(idx).map_or(false, |v| cmp_le(v, &cmp_val)))
-                    .collect()
-            }
-
-            Some("<") => {
-                let cmp_val = raw_val.clone().unwrap_or(Value::Null);
-                result
-                    .into_iter()
-                    .filter(|row| row.get(idx).map_or(false, |v| cmp_lt(v, &cmp_val)))
-                    .collect()
-            }
-
-            Some("contains") | Some("like") => {
-                let needle = raw_val
-                    .as_ref()
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_lowercase();
-
-                result
-                    .into_iter()
-                    .filter(|row| {
-                        row.get(idx)
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("")
-                            .to_lowercase()
-                            .contains(&needle)
-                    })
-                    .collect()
-            }
-
-            Some(other) => {
-                return Err(PluginError::InvalidRequest {
-                    message: format!("Unsupported filter operator: {}", other),
-                });
-            }
-
-            None => result,
-        };
+fn compile_filter(f: &FilterExpr, schema: &Schema) -> Result<RowFilter, PluginError> {
+    let col = resolve_column_id(f)
+        .filter(|s| !s.trim().is_empty())
+        .ok_or_else(|| PluginError::InvalidRequest {
+            message: "Filter is missing column_id/id".to_string(),
+        })?;
+
+    let expr = normalize_op(f.expression.as_deref());
+
+    let idx = match schema.col_index.get(&col) {
+        Some(&i) => i,
+        None => {
+            return Err(PluginError::UnknownColumn {
+                message: format!("Unknown column in filter: {}", col),
+            })
+        }
+    };
+
+    let raw_val = f.value.as_ref().map(normalize_value);
+    let cmp_val = || raw_val.clone().unwrap_or(Value::Null);
+
+    let test = match expr {
+        Some("is not null") => Test::NotNull,
+
+        Some("in") => {
+            let vals = match &f.value {
+                Some(Value::Array(v)) => v.clone(),
+                Some(v) => vec![v.clone()],
+                // zonder waarde geen filter
+                None => return Ok(RowFilter { idx, hierarchy: None, test: Test::Any }),
+            };
+            Test::In(vals.iter().map(normalize_value).collect())
+        }
+
+        Some("=") | Some("==") => Test::Eq(cmp_val()),
+        Some("!=") | Some("!==") => Test::Ne(cmp_val()),
+        Some(">=") => Test::Ge(cmp_val()),
+        Some(">") => Test::Gt(cmp_val()),
+        Some("<=") => Test::Le(cmp_val()),
+        Some("<") => Test::Lt(cmp_val()),
+
+        Some("contains") | Some("like") => Test::Contains(
+            raw_val
+                .as_ref()
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_lowercase(),
+        ),
+
+        Some(other) => {
+            return Err(PluginError::InvalidRequest {
+                message: format!("Unsupported filter operator: {}", other),
+            });
+        }
+
+        None => Test::Any,
+    };
+
+    Ok(RowFilter {
+        idx,
+        hierarchy: schema.hierarchies.get(&col).cloned(),
+        test,
+    })
+}
+
+// The rows that pass every filter, copied.
+pub(crate) fn filter_rows(
+    rows: &[Vec<Value>],
+    filters: &[RowFilter],
+    cancel: &Cancellation,
+) -> Result<Vec<Vec<Value>>, PluginError> {
+    let mut out = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        if i.is_multiple_of(CHECK_EVERY) {
+            cancel.check()?;
+        }
+        if matches_all(filters, row) {
+            out.push(row.clone());
+        }
     }
+    Ok(out)
+}
+
+pub fn apply_filters(
+    rows: &[Vec<Value>],
+    filters: Option<Vec<FilterExpr>>,
+    schema: &Schema,
+) -> Result<Vec<Vec<Value>>, PluginError> {
+    apply_filters_until(rows, filters, schema, &Cancellation::default())
+}
 
-    Ok(result)
-}
\ No newline at end of file
+// apply_filters that stops with a Timeout error once `cancel` fires.
+pub fn apply_filters_until(
+    rows: &[Vec<Value>],
+    filters: Option<Vec<FilterExpr>>,
+    schema: &Schema,
+    cancel: &Cancellation,
+) -> Result<Vec<Vec<Value>>, PluginError> {
+    let filters = compile_filters(filters.as_deref().unwrap_or_default(), schema)?;
+    filter_rows(rows, &filters, cancel)
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::errors::PluginError;
+
+// A group column drilled to a level of its hierarchy.
+#[derive(Debug, Clone)]
+pub struct Drill {
+    pub hierarchy: Hierarchy,
+    pub depth: usize,
+}
+
+// Multi-level hierarchy over a column whose rows store the leaf value,
+// e.g. Division > Segment > Category. Every leaf maps to its full path (root first).
+#[derive(Debug, Clone)]
+pub struct Hierarchy {
+    pub levels: Vec<String>,
+    paths: HashMap<String, Vec<String>>,
+}
+
+impl Hierarchy {
+    // Builds leaf paths from (child, parent) pairs; nodes without a parent are roots.
+    pub fn from_parents(levels: &[&str], edges: &[(&str, Option<&str>)]) -> Self {
+        let parents: HashMap<&str, Option<&str>> = edges.iter().copied().collect();
+        let depth = levels.len();
+
+        let mut paths = HashMap::new();
+        for (node, _) in edges {
+            let mut path = vec![node.to_string()];
+            let mut cur = parents.get(node).copied().flatten();
+            while let Some(p) = cur {
+                if path.len() > depth {
+                    break;
+                }
+                path.insert(0, p.to_string());
+                cur = parents.get(p).copied().flatten();
+            }
+            if path.len() == depth {
+                paths.insert(node.to_string(), path);
+            }
+        }
+
+        Hierarchy {
+            levels: levels.iter().map(|s| s.to_string()).collect(),
+            paths,
+        }
+    }
+
+    // Luzmo sends drill levels as 1-based numbers; level names are accepted too.
+    // No level means the leaf level.
+    pub fn depth_for(&self, level: Option<&str>) -> Result<usize, PluginError> {
+        let Some(level) = level.map(str::trim).filter(|l| !l.is_empty()) else {
+            return Ok(self.levels.len());
+        };
+
+        let depth = match level.parse::<usize>() {
+            Ok(n) => Some(n),
+            Err(_) => self.levels.iter().position(|l| l == level).map(|i| i + 1),
+        };
+
+        depth
+            .filter(|d| (1..=self.levels.len()).contains(d))
+            .ok_or_else(|| PluginError::InvalidRequest {
+                message: format!("Unknown hierarchy level: {}", level),
+            })
+    }
+
+    pub fn path(&self, leaf: &Value) -> Option<&[String]> {
+        leaf.as_str().and_then(|s| self.paths.get(s)).map(|p| p.as_slice())
+    }
+
+    // The ancestor of `leaf` at `depth` (1 = root). Unknown leaves are returned as-is.
+    pub fn node_at(&self, leaf: &Value, depth: usize) -> Value {
+        match self.path(leaf).and_then(|p| p.get(depth.saturating_sub(1))) {
+            Some(node) => Value::String(node.clone()),
+            None => leaf.clone(),
+        }
+    }
+
+    // All values a filter can match for this leaf: every node on its path.
+    pub fn path_values(&self, leaf: &Value) -> Vec<Value> {
+        match self.path(leaf) {
+            Some(p) => p.iter().cloned().map(Value::String).collect(),
+            None => vec![leaf.clone()],
+        }
+    }
+}
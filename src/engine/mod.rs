@@ -1,5 +1,18 @@
+pub mod access;
 pub mod aggregation;
+pub mod bins;
+pub mod cache;
+pub mod cancel;
+pub mod comparison;
+pub mod context;
 pub mod dataset;
 pub mod execute;
+pub mod explain;
 pub mod filters;
-pub mod plan;
\ No newline at end of file
+pub mod hierarchy;
+pub mod numeric;
+pub mod plan;
+pub mod pool;
+pub mod rows;
+pub mod stats;
+pub mod window;
@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde_json::{json, Value};
+
+use crate::utils::sanitize::normalize_value;
+
+// Output format of a numeric column, configured per column in the dataset schema.
+// `decimal` columns (currency) accumulate in exact decimal arithmetic;
+// `integer` columns return sum/min/max as JSON integers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberFormat {
+    pub precision: u32,
+    pub decimal: bool,
+    pub integer: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        NumberFormat {
+            precision: 2,
+            decimal: false,
+            integer: false,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn round(&self, x: f64) -> Value {
+        if self.integer {
+            return json!(x.round() as i64);
+        }
+        let factor = 10f64.powi(self.precision as i32);
+        json!((x * factor).round() / factor)
+    }
+
+    pub fn round_decimal(&self, d: Decimal) -> Value {
+        let precision = if self.integer { 0 } else { self.precision };
+        let d = d.round_dp_with_strategy(precision, RoundingStrategy::MidpointAwayFromZero);
+        if self.integer {
+            return d.to_i64().map(|n| json!(n)).unwrap_or(Value::Null);
+        }
+        d.to_f64().map(|n| json!(n)).unwrap_or(Value::Null)
+    }
+
+    // Averages and other ratios are never integers, even over integer columns,
+    // and keep at least the default number of decimals.
+    pub fn fractional(&self) -> NumberFormat {
+        NumberFormat {
+            integer: false,
+            precision: self.precision.max(NumberFormat::default().precision),
+            ..*self
+        }
+    }
+}
+
+// Parses the JSON number through its shortest decimal text, so 0.1 becomes
+// exactly 0.1 instead of the nearest binary float.
+pub fn to_decimal(v: &Value) -> Option<Decimal> {
+    match normalize_value(v) {
+        Value::Number(n) => Decimal::from_str(&n.to_string())
+            .or_else(|_| Decimal::from_scientific(&n.to_string()))
+            .ok(),
+        _ => None,
+    }
+}
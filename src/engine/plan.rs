@@ -1,12 +1,17 @@
-use std::collections::HashMap;
-
-use crate::luzmo::types::{Column, FilterExpr, QueryRequest};
+use crate::engine::bins::validate_bins;
+use crate::engine::dataset::Schema;
+use crate::engine::hierarchy::Drill;
+use crate::engine::numeric::NumberFormat;
+use crate::engine::window::validate_window;
+use crate::luzmo::types::{BinSpec, Column, FilterExpr, QueryRequest};
 use crate::errors::PluginError;
 #[derive(Debug, Clone)]
 pub struct GroupCol {
     pub id: String,
     pub level: Option<String>,
     pub col_type: Option<String>,
+    pub bins: Option<BinSpec>,
+    pub drill: Option<Drill>,
 }
 
 #[derive(Debug, Clone)]
@@ -14,6 +19,11 @@ pub struct Measure {
     pub id: String,
     pub agg: String,
     pub col_type: Option<String>,
+    pub window: Option<String>,
+    pub window_size: Option<usize>,
+    pub comparison: Option<String>,
+    pub comparison_output: Option<String>,
+    pub format: NumberFormat,
 }
 
 #[derive(Debug, Clone)]
@@ -27,14 +37,22 @@ pub struct QueryPlan {
     pub requested_cols: Vec<Column>,
 }
 
-fn col_id(c: &Column) -> String {
+pub(crate) fn col_id(c: &Column) -> String {
     c.column_id
         .clone()
         .or_else(|| if c.id.is_empty() { None } else { Some(c.id.clone()) })
         .unwrap_or_default()
 }
 
-pub fn build_plan(req: &QueryRequest,col_index: HashMap<String, usize>,) -> Result<QueryPlan, PluginError> {
+// "SUM", " sum" en "sum" zijn dezelfde aggregatie
+pub(crate) fn normalize_agg(c: &Column) -> String {
+    c.aggregation.as_deref().unwrap_or("").trim().to_lowercase()
+}
+
+pub fn build_plan(
+    req: &QueryRequest,
+    schema: &Schema,
+) -> Result<QueryPlan, PluginError> {
     let dataset_id = req
         .dataset_id
         .clone()
@@ -49,34 +67,54 @@ pub fn build_plan(req: &QueryRequest,col_index: HashMap<String, usize>,) -> Resu
         if cid == "*" {
             continue;
         }
-        if !cid.is_empty() && !col_index.contains_key(&cid) {
+        if !cid.is_empty() && !schema.col_index.contains_key(&cid) {
         return Err(PluginError::UnknownColumn {
             message: format!("Unknown column in request: {}", cid),
         });        
         }
     }
 
-    let has_agg = cols.iter().any(|c| c.aggregation.as_deref().unwrap_or("").len() > 0);
+    let has_agg = cols.iter().any(|c| !normalize_agg(c).is_empty());
 
     let mut group_cols = vec![];
     let mut measures = vec![];
 
     for c in &cols {
         let cid = col_id(c);
-        let agg = c.aggregation.clone().unwrap_or_default();
+        let agg = normalize_agg(c);
 
         if agg.is_empty() {
+            if let Some(spec) = &c.bins {
+                validate_bins(&cid, spec)?;
+            }
+            let drill = match schema.hierarchies.get(&cid) {
+                Some(h) => Some(Drill {
+                    hierarchy: h.clone(),
+                    depth: h.depth_for(c.level.as_deref())?,
+                }),
+                None => None,
+            };
             group_cols.push(GroupCol {
                 id: cid,
                 level: c.level.clone(),
                 col_type: c.r#type.clone(),
+                bins: c.bins.clone(),
+                drill,
             });
         } else {
-            measures.push(Measure {
+            let format = schema.format(&cid);
+            let measure = Measure {
                 id: cid,
                 agg,
                 col_type: c.r#type.clone(),
-            });
+                window: c.window.clone(),
+                window_size: c.window_size,
+                comparison: c.comparison.clone(),
+                comparison_output: c.comparison_output.clone(),
+                format,
+            };
+            validate_window(&measure)?;
+            measures.push(measure);
         }
     }
 
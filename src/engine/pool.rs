@@ -0,0 +1,137 @@
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::engine::cancel::Cancellation;
+use crate::errors::PluginError;
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_concurrent: usize,
+    // Queries allowed to wait for a slot; beyond that they are rejected.
+    pub max_queued: usize,
+}
+
+impl PoolConfig {
+    pub fn from_env() -> Self {
+        let num = |name: &str, default: usize| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default)
+        };
+        let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        PoolConfig {
+            max_concurrent: num("LUZMO_PLUGIN_MAX_CONCURRENT_QUERIES", cores).max(1),
+            max_queued: num("LUZMO_PLUGIN_QUERY_QUEUE", 64),
+        }
+    }
+}
+
+// Leaves the wait queue, also when the waiting request is dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// One of the pool's slots; the query keeps running (or streaming) as long as it
+// is held.
+pub struct Slot {
+    _permit: OwnedSemaphorePermit,
+}
+
+// Runs queries on the blocking thread pool, at most `max_concurrent` at a time,
+// so the async workers stay free for /health, /authorize and the like.
+pub struct QueryPool {
+    config: PoolConfig,
+    slots: Arc<Semaphore>,
+    queued: AtomicUsize,
+}
+
+impl QueryPool {
+    pub fn new(config: PoolConfig) -> Self {
+        QueryPool {
+            slots: Arc::new(Semaphore::new(config.max_concurrent)),
+            queued: AtomicUsize::new(0),
+            config,
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.config.max_concurrent - self.slots.available_permits()
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    pub async fn run<F, T>(&self, job: F) -> Result<T, PluginError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.run_until(&Cancellation::default(), job).await
+    }
+
+    // run, but waiting for a slot ends with a Timeout error at the query's deadline.
+    pub async fn run_until<F, T>(&self, cancel: &Cancellation, job: F) -> Result<T, PluginError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.run_holding(cancel, job).await.map(|(out, _slot)| out)
+    }
+
+    // run_until that hands the slot back with the result, for work that goes on
+    // after the job (a streamed response).
+    pub async fn run_holding<F, T>(&self, cancel: &Cancellation, job: F) -> Result<(T, Slot), PluginError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = match self.slots.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let max = self.config.max_queued;
+                self.queued
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| (n < max).then_some(n + 1))
+                    .map_err(|_| PluginError::Overloaded {
+                        message: format!(
+                            "Too many queries: {} running, {} waiting",
+                            self.config.max_concurrent, max
+                        ),
+                    })?;
+                let _queued = Queued(&self.queued);
+                let acquire = self.slots.clone().acquire_owned();
+                let acquired = match cancel.deadline() {
+                    Some(deadline) => tokio::time::timeout_at(deadline.into(), acquire)
+                        .await
+                        .map_err(|_| cancel.expired())?,
+                    None => acquire.await,
+                };
+                acquired.map_err(|e| PluginError::InternalError {
+                    message: format!("Query pool closed: {}", e),
+                })?
+            }
+        };
+
+        // The slot is held by the job itself: a cancelled request keeps it
+        // until the query actually stops.
+        actix_web::web::block(move || (job(), Slot { _permit: permit }))
+            .await
+            .map_err(|e| PluginError::InternalError {
+                message: format!("Query worker failed: {}", e),
+            })
+    }
+}
+
+pub fn query_pool() -> &'static QueryPool {
+    static POOL: OnceLock<QueryPool> = OnceLock::new();
+    POOL.get_or_init(|| QueryPool::new(PoolConfig::from_env()))
+}
@@ -0,0 +1,240 @@
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::engine::access::Masking;
+use crate::engine::aggregation::bucket_date;
+use crate::engine::cancel::{Cancellation, CHECK_EVERY};
+use crate::engine::filters::{matches_all, RowFilter};
+use crate::engine::hierarchy::Hierarchy;
+use crate::engine::plan::col_id;
+use crate::errors::PluginError;
+use crate::luzmo::types::Column;
+use crate::utils::sanitize::{normalize_value, sanitize_json_value};
+
+enum Cell {
+    Plain(usize),
+    Date(usize, String),
+    Node(usize, Hierarchy, usize),
+}
+
+// The caller's rows that pass every filter, read one at a time from the shared
+// dataset: row-level security on the real values, then masking, then the
+// request filters. Only the row being produced is copied.
+pub struct FilteredRows {
+    data: Arc<Vec<Vec<Value>>>,
+    pos: usize,
+    row_filters: Vec<RowFilter>,
+    masking: Masking,
+    filters: Vec<RowFilter>,
+    cancel: Cancellation,
+    // Positions of the matching rows once counted, so they are not filtered again.
+    matches: Option<Vec<usize>>,
+}
+
+impl FilteredRows {
+    pub(crate) fn new(
+        data: Arc<Vec<Vec<Value>>>,
+        row_filters: Vec<RowFilter>,
+        masking: Masking,
+        filters: Vec<RowFilter>,
+        cancel: Cancellation,
+    ) -> Self {
+        FilteredRows {
+            data,
+            pos: 0,
+            row_filters,
+            masking,
+            filters,
+            cancel,
+            matches: None,
+        }
+    }
+
+    fn mask<'r>(&self, row: &'r [Value]) -> Cow<'r, [Value]> {
+        if self.masking.is_empty() {
+            return Cow::Borrowed(row);
+        }
+        let mut masked = row.to_vec();
+        self.masking.apply(&mut masked);
+        Cow::Owned(masked)
+    }
+
+    // The caller's view of `row`, if it passes every filter.
+    fn admit<'r>(&self, row: &'r [Value]) -> Option<Cow<'r, [Value]>> {
+        if !matches_all(&self.row_filters, row) {
+            return None;
+        }
+        let row = self.mask(row);
+        matches_all(&self.filters, &row).then_some(row)
+    }
+
+    fn next_match(&mut self) -> Result<Option<Vec<Value>>, PluginError> {
+        if let Some(matches) = &self.matches {
+            let Some(&i) = matches.get(self.pos) else {
+                return Ok(None);
+            };
+            self.pos += 1;
+            if self.pos.is_multiple_of(CHECK_EVERY) {
+                self.cancel.check()?;
+            }
+            return Ok(Some(self.mask(&self.data[i]).into_owned()));
+        }
+        while self.pos < self.data.len() {
+            self.pos += 1;
+            if self.pos.is_multiple_of(CHECK_EVERY) {
+                self.cancel.check()?;
+            }
+            if let Some(row) = self.admit(&self.data[self.pos - 1]) {
+                return Ok(Some(row.into_owned()));
+            }
+        }
+        Ok(None)
+    }
+
+    // How many rows will match. Only their positions are kept: reading the rows
+    // afterwards masks just those, without filtering the dataset a second time.
+    pub(crate) fn count(&mut self) -> Result<usize, PluginError> {
+        let mut matches = Vec::new();
+        for (i, row) in self.data.iter().enumerate().skip(self.pos) {
+            if i.is_multiple_of(CHECK_EVERY) {
+                self.cancel.check()?;
+            }
+            if self.admit(row).is_some() {
+                matches.push(i);
+            }
+        }
+        let n = matches.len();
+        self.pos = 0;
+        self.matches = Some(matches);
+        Ok(n)
+    }
+
+    pub(crate) fn collect_rows(mut self) -> Result<Vec<Vec<Value>>, PluginError> {
+        let mut out = Vec::new();
+        while let Some(row) = self.next_match()? {
+            out.push(row);
+        }
+        Ok(out)
+    }
+}
+
+// Raw-mode output, produced one row at a time from the filtered rows so large
+// results can be streamed instead of built up front. A deadline or cancel that
+// fires halfway ends it with the error.
+pub struct RawRows {
+    rows: FilteredRows,
+    cells: Option<Vec<Cell>>,
+    hidden: Vec<usize>,
+    remaining: usize,
+}
+
+impl RawRows {
+    // All columns except the hidden ones (no columns requested). `matching` is
+    // FilteredRows::count.
+    pub(crate) fn all(rows: FilteredRows, matching: usize, hidden: Vec<usize>, limit: Option<usize>) -> Self {
+        RawRows {
+            remaining: limit.unwrap_or(usize::MAX).min(matching),
+            rows,
+            cells: None,
+            hidden,
+        }
+    }
+
+    // The requested columns, with date bucketing and hierarchy levels applied.
+    pub(crate) fn columns(
+        rows: FilteredRows,
+        matching: usize,
+        requested: &[Column],
+        col_index: &HashMap<String, usize>,
+        hierarchies: &HashMap<String, Hierarchy>,
+        limit: Option<usize>,
+    ) -> Result<Self, PluginError> {
+        let mut cells = Vec::with_capacity(requested.len());
+        for c in requested {
+            let cid = col_id(c);
+            let idx = *col_index.get(&cid).ok_or_else(|| PluginError::UnknownColumn {
+                message: format!("Unknown column in request: {}", cid),
+            })?;
+
+            let cell = match (cid.as_str(), c.level.as_deref(), hierarchies.get(&cid)) {
+                ("date", Some(level), _) => Cell::Date(idx, level.to_string()),
+                (_, level, Some(h)) => Cell::Node(idx, h.clone(), h.depth_for(level)?),
+                _ => Cell::Plain(idx),
+            };
+            cells.push(cell);
+        }
+
+        Ok(RawRows {
+            remaining: limit.unwrap_or(usize::MAX).min(matching),
+            rows,
+            cells: Some(cells),
+            hidden: vec![],
+        })
+    }
+
+    // Rows still to come (unless an error cuts it short).
+    pub fn len(&self) -> usize {
+        self.remaining
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn project(&self, r: &[Value]) -> Vec<Value> {
+        let Some(cells) = &self.cells else {
+            return r
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !self.hidden.contains(i))
+                .map(|(_, cell)| sanitize_json_value(normalize_value(cell)))
+                .collect();
+        };
+
+        cells
+            .iter()
+            .map(|cell| {
+                let v = match cell {
+                    Cell::Plain(idx) => normalize_value(r.get(*idx).unwrap_or(&Value::Null)),
+                    Cell::Date(idx, level) => {
+                        bucket_date(&normalize_value(r.get(*idx).unwrap_or(&Value::Null)), level)
+                    }
+                    Cell::Node(idx, h, depth) => {
+                        h.node_at(&normalize_value(r.get(*idx).unwrap_or(&Value::Null)), *depth)
+                    }
+                };
+                sanitize_json_value(v)
+            })
+            .collect()
+    }
+}
+
+impl Iterator for RawRows {
+    type Item = Result<Vec<Value>, PluginError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match self.rows.next_match() {
+            Ok(Some(r)) => {
+                self.remaining -= 1;
+                Some(Ok(self.project(&r)))
+            }
+            Ok(None) => {
+                self.remaining = 0;
+                None
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
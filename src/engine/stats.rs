@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+// What a query did, for metrics and, with `options.include_metadata`, for the
+// caller (X-Query-Metadata response header).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryStats {
+    // Rows read from the dataset, before row-level security and filters.
+    pub rows_scanned: usize,
+    pub rows_filtered: usize,
+    // Groups produced by the aggregation, before the limit; 0 in raw mode.
+    pub groups: usize,
+    pub rows_returned: usize,
+    // Served from the result cache; the counts are those of the run that filled it,
+    // the timings are zero.
+    #[serde(default)]
+    pub cache_hit: bool,
+    // Always false for now: the demo dataset is in memory, nothing is pushed down.
+    pub pushdown: bool,
+    pub timings: PhaseTimings,
+}
+
+// Milliseconds per phase. serialize_ms is filled in by the HTTP handler.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    pub filter_ms: f64,
+    pub plan_ms: f64,
+    pub aggregate_ms: f64,
+    pub serialize_ms: f64,
+}
+
+pub fn elapsed_ms(since: Instant) -> f64 {
+    (since.elapsed().as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
@@ -0,0 +1,154 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::engine::aggregation::round2f;
+use crate::engine::cancel::{Cancellation, CHECK_EVERY};
+use crate::engine::numeric::NumberFormat;
+use crate::engine::plan::{Measure, QueryPlan};
+use crate::errors::PluginError;
+
+// Window measures: computed over the aggregated groups, not over raw rows.
+pub const WINDOW_FUNCTIONS: [&str; 6] = [
+    "running_sum",
+    "moving_avg",
+    "rank",
+    "dense_rank",
+    "percent_of_total",
+    "difference",
+];
+
+pub fn validate_window(m: &Measure) -> Result<(), PluginError> {
+    let Some(w) = m.window.as_deref() else {
+        return Ok(());
+    };
+    if !WINDOW_FUNCTIONS.contains(&w) {
+        return Err(PluginError::InvalidRequest {
+            message: format!("Unsupported window function: {}", w),
+        });
+    }
+    if w == "moving_avg" && m.window_size.unwrap_or(0) == 0 {
+        return Err(PluginError::InvalidRequest {
+            message: format!("moving_avg on {} requires window_size >= 1", m.id),
+        });
+    }
+    Ok(())
+}
+
+// Ordering along the date group column if there is one, otherwise the last group column.
+// The remaining group columns form the partition.
+fn order_index(plan: &QueryPlan) -> Option<usize> {
+    plan.group_cols
+        .iter()
+        .position(|g| g.id == "date")
+        .or_else(|| plan.group_cols.len().checked_sub(1))
+}
+
+fn window_values(func: &str, size: usize, fmt: NumberFormat, vals: &[Option<f64>]) -> Vec<Value> {
+    match func {
+        "running_sum" => {
+            let mut acc = 0.0;
+            vals.iter()
+                .map(|v| {
+                    acc += v.unwrap_or(0.0);
+                    fmt.round(acc)
+                })
+                .collect()
+        }
+        "moving_avg" => (0..vals.len())
+            .map(|i| {
+                let from = (i + 1).saturating_sub(size);
+                let window: Vec<f64> = vals[from..=i].iter().flatten().copied().collect();
+                if window.is_empty() {
+                    Value::Null
+                } else {
+                    fmt.fractional().round(window.iter().sum::<f64>() / window.len() as f64)
+                }
+            })
+            .collect(),
+        "rank" | "dense_rank" => {
+            let mut sorted: Vec<f64> = vals.iter().flatten().copied().collect();
+            sorted.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+            let mut distinct = sorted.clone();
+            distinct.dedup();
+
+            vals.iter()
+                .map(|v| match v {
+                    Some(n) if func == "rank" => {
+                        json!(sorted.iter().take_while(|x| *x > n).count() + 1)
+                    }
+                    Some(n) => json!(distinct.iter().take_while(|x| *x > n).count() + 1),
+                    None => Value::Null,
+                })
+                .collect()
+        }
+        "percent_of_total" => {
+            let total: f64 = vals.iter().flatten().sum();
+            vals.iter()
+                .map(|v| match v {
+                    Some(n) if total != 0.0 => json!(round2f(n / total * 100.0)),
+                    Some(_) => json!(0.0),
+                    None => Value::Null,
+                })
+                .collect()
+        }
+        "difference" => vals
+            .iter()
+            .enumerate()
+            .map(|(i, v)| match (i.checked_sub(1).and_then(|p| vals[p]), v) {
+                (Some(prev), Some(cur)) => fmt.round(cur - prev),
+                _ => Value::Null,
+            })
+            .collect(),
+        _ => vec![Value::Null; vals.len()],
+    }
+}
+
+// Rewrites the measure cells of aggregated rows (group values first, then measures)
+// for every measure that requested a window function. Stops with a Timeout error
+// once `cancel` fires.
+pub fn apply_windows_until(rows: &mut [Vec<Value>], plan: &QueryPlan, cancel: &Cancellation) -> Result<(), PluginError> {
+    if plan.measures.iter().all(|m| m.window.is_none()) {
+        return Ok(());
+    }
+
+    let n_groups = plan.group_cols.len();
+    let order_idx = order_index(plan);
+
+    let mut partitions: HashMap<String, Vec<usize>> = HashMap::new();
+    for (ri, row) in rows.iter().enumerate() {
+        if ri.is_multiple_of(CHECK_EVERY) {
+            cancel.check()?;
+        }
+        let pkey: Vec<&Value> = row[..n_groups]
+            .iter()
+            .enumerate()
+            .filter(|(gi, _)| Some(*gi) != order_idx)
+            .map(|(_, v)| v)
+            .collect();
+        let key = serde_json::to_string(&pkey).unwrap_or_default();
+        partitions.entry(key).or_default().push(ri);
+    }
+
+    // execute_aggregation returns groups sorted on all group columns, so inside a
+    // partition the rows are already ordered along the order column.
+    for members in partitions.values() {
+        cancel.check()?;
+        for (mi, m) in plan.measures.iter().enumerate() {
+            let Some(func) = m.window.as_deref() else {
+                continue;
+            };
+
+            let col = n_groups + mi;
+            let vals: Vec<Option<f64>> = members.iter().map(|ri| rows[*ri][col].as_f64()).collect();
+            let out = window_values(func, m.window_size.unwrap_or(1), m.format, &vals);
+
+            for (ri, v) in members.iter().zip(out) {
+                rows[*ri][col] = v;
+            }
+        }
+    }
+
+    Ok(())
+}
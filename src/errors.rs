@@ -1,4 +1,4 @@
-use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{http::header, http::StatusCode, HttpResponse, ResponseError};
 use serde::Serialize;
 use std::fmt;
 
@@ -21,9 +21,26 @@ pub enum PluginError {
     DatasetNotFound { message: String },
     InvalidRequest { message: String },
     InternalError { message: String },
+    Timeout { message: String },
+    Overloaded { message: String },
+    RateLimited { message: String, retry_after: u64 },
 }
 
 impl PluginError {
+    // Variant name, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PluginError::UnknownColumn { .. } => "UnknownColumn",
+            PluginError::UnsupportedAggregation { .. } => "UnsupportedAggregation",
+            PluginError::DatasetNotFound { .. } => "DatasetNotFound",
+            PluginError::InvalidRequest { .. } => "InvalidRequest",
+            PluginError::InternalError { .. } => "InternalError",
+            PluginError::Timeout { .. } => "Timeout",
+            PluginError::Overloaded { .. } => "Overloaded",
+            PluginError::RateLimited { .. } => "RateLimited",
+        }
+    }
+
     fn meta(&self) -> (StatusCode, &'static str, &str) {
         match self {
             PluginError::UnknownColumn { message } => (StatusCode::BAD_REQUEST, "Unknown column", message),
@@ -31,6 +48,9 @@ impl PluginError {
             PluginError::DatasetNotFound { message } => (StatusCode::NOT_FOUND, "Unknown dataset", message),
             PluginError::InvalidRequest { message } => (StatusCode::BAD_REQUEST, "Invalid request", message),
             PluginError::InternalError { message } => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error", message),
+            PluginError::Timeout { message } => (StatusCode::GATEWAY_TIMEOUT, "Query timeout", message),
+            PluginError::Overloaded { message } => (StatusCode::SERVICE_UNAVAILABLE, "Service overloaded", message),
+            PluginError::RateLimited { message, .. } => (StatusCode::TOO_MANY_REQUESTS, "Too many requests", message),
         }
     }
 }
@@ -42,6 +62,8 @@ impl fmt::Display for PluginError {
     }
 }
 
+impl std::error::Error for PluginError {}
+
 impl ResponseError for PluginError {
     fn status_code(&self) -> StatusCode {
         self.meta().0
@@ -49,7 +71,17 @@ impl ResponseError for PluginError {
 
     fn error_response(&self) -> HttpResponse {
         let (status, description, message) = self.meta();
-        HttpResponse::build(status).json(ErrorResponse {
+        let mut resp = HttpResponse::build(status);
+        match self {
+            PluginError::Overloaded { .. } => {
+                resp.insert_header((header::RETRY_AFTER, 1));
+            }
+            PluginError::RateLimited { retry_after, .. } => {
+                resp.insert_header((header::RETRY_AFTER, *retry_after));
+            }
+            _ => {}
+        }
+        resp.json(ErrorResponse {
             r#type: ErrorType {
                 code: status.as_u16(),
                 description: description.to_string(),
@@ -13,6 +13,28 @@ pub struct Column {
     pub level: Option<String>,
     #[serde(default)]
     pub r#type: Option<String>,
+    #[serde(default)]
+    pub window: Option<String>,
+    #[serde(default)]
+    pub window_size: Option<usize>,
+    #[serde(default)]
+    pub comparison: Option<String>,
+    #[serde(default)]
+    pub comparison_output: Option<String>,
+    #[serde(default)]
+    pub bins: Option<BinSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BinSpec {
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(default)]
+    pub origin: Option<f64>,
+    #[serde(default)]
+    pub edges: Option<Vec<f64>>,
+    #[serde(default)]
+    pub include_empty: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -29,7 +51,7 @@ pub struct FilterExpr {
     pub value: Option<Value>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct SortExpr {
     #[serde(default)]
@@ -1,15 +1,22 @@
+use actix_web::middleware::{from_fn, Compress};
 use actix_web::{web, App, HttpServer};
 
-mod engine;
-mod errors;
-mod luzmo;
-mod server;
-mod utils;
+use luzmo_plugin::engine::access;
+use luzmo_plugin::engine::dataset::demo_schema;
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{credentials_configured, verifier_from_env};
+use luzmo_plugin::utils::logging;
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::ratelimit::{RateLimitConfig, RateLimiter};
+use luzmo_plugin::utils::secret;
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
-    env_logger::init();
+    // RUST_LOG=debug logt ook headers en (geredigeerde) bodies
+    logging::init();
 
     let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
     let port: u16 = std::env::var("PORT")
@@ -17,18 +24,54 @@ async fn main() -> std::io::Result<()> {
         .and_then(|s| s.parse().ok())
         .unwrap_or(3000);
 
-    println!("SERVER: Rust HTTP plugin listening on http://{}:{}", host, port);
+    log::info!(host = host.as_str(), port = port; "Rust HTTP plugin listening");
 
-    HttpServer::new(|| {
+    secret::reload_secrets().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    actix_web::rt::spawn(secret::reload_secrets_periodically());
+    let signing = web::Data::new(SigningConfig::from_env());
+    signing.validate().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let verifier = web::Data::from(
+        verifier_from_env().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+    );
+    let tenants = web::Data::new(
+        Tenants::from_env().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+    );
+    let policies = web::Data::new(
+        RowPolicies::from_env().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+    );
+    let limiter = web::Data::new(RateLimiter::new(RateLimitConfig::from_env()));
+    access::check_hash_salt(&demo_schema(), access::configured_hash_salt().as_deref())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // met AllowAll kiest de caller zelf zijn user via X-Key
+    if policies.is_configured() && !credentials_configured() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "LUZMO_PLUGIN_POLICIES_FILE requires LUZMO_PLUGIN_CREDENTIALS_FILE",
+        ));
+    }
+
+    HttpServer::new(move || {
         App::new()
+            .wrap(from_fn(server::middleware::rate_limit))
+            .wrap(from_fn(server::middleware::track_metrics))
+            .wrap(from_fn(server::middleware::request_id))
+            // buitenste laag: gzip / brotli / zstd volgens Accept-Encoding
+            .wrap(Compress::default())
+            .app_data(verifier.clone())
+            .app_data(tenants.clone())
+            .app_data(policies.clone())
+            .app_data(signing.clone())
+            .app_data(limiter.clone())
             .route("/", web::get().to(server::health::root))
             .route("/health", web::get().to(server::health::health))
+            .route("/metrics", web::get().to(server::metrics::handle_metrics))
             .route("/datasets", web::get().to(server::dataset::handle_datasets))
             .route("/datasets", web::post().to(server::dataset::handle_datasets))
             .route("/query", web::post().to(server::query::handle_query))
+            .route("/query/explain", web::post().to(server::query::handle_explain))
             .route("/authorize", web::post().to(server::authorize::authorize))
     })
     .bind((host.as_str(), port))?
     .run()
     .await
-}
\ No newline at end of file
+}
@@ -0,0 +1,49 @@
+use actix_web::{HttpRequest, HttpResponse};
+use serde_json::json;
+
+use crate::utils::credentials::{identify, CredentialVerifier, Identity};
+use crate::utils::secret::{check_lockout, check_secret, record_auth_failure, record_auth_success};
+use crate::utils::signing::{check_signature, SigningConfig};
+use crate::utils::tenants::Tenants;
+
+// Shared by /authorize, /datasets and /query: a signed request or the X-Secret
+// (global or a tenant's own), then the per-user credentials, then the tenant
+// the caller belongs to.
+pub fn authenticate(
+    req: &HttpRequest,
+    body: &[u8],
+    verifier: &dyn CredentialVerifier,
+    tenants: &Tenants,
+    signing: &SigningConfig,
+) -> Result<Identity, HttpResponse> {
+    check_lockout(req)?;
+    let signed = check_signature(req, body, signing)?;
+
+    let got = req
+        .headers()
+        .get("X-Secret")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    let secret_tenant = tenants.by_secret(got).map(|t| t.id.clone());
+    if !signed && secret_tenant.is_none() {
+        check_secret(req)?;
+    }
+
+    let mut identity = identify(req, verifier)?;
+
+    match (&identity.tenant, secret_tenant) {
+        (Some(t), Some(s)) if *t != s => {
+            record_auth_failure(req, "credentials of another tenant");
+            return Err(HttpResponse::Unauthorized().json(json!({
+                "type": { "code": 401, "description": "Unauthorized" },
+                "message": "Credentials do not belong to this tenant"
+            })));
+        }
+        (None, Some(s)) => identity.tenant = Some(s),
+        _ => {}
+    }
+
+    record_auth_success(req);
+    Ok(identity)
+}
@@ -1,12 +1,21 @@
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde_json::json;
 
-use crate::utils::secret::check_secret;
+use crate::server::auth::authenticate;
+use crate::utils::credentials::CredentialVerifier;
+use crate::utils::signing::SigningConfig;
+use crate::utils::tenants::Tenants;
 
-pub async fn authorize(req: HttpRequest) -> HttpResponse {
-    if let Err(resp) = check_secret(&req) {
+pub async fn authorize(
+    req: HttpRequest,
+    body: web::Bytes,
+    verifier: web::Data<dyn CredentialVerifier>,
+    tenants: web::Data<Tenants>,
+    signing: web::Data<SigningConfig>,
+) -> HttpResponse {
+    if let Err(resp) = authenticate(&req, &body, verifier.get_ref(), &tenants, &signing) {
         return resp; 
     }
 
     HttpResponse::Ok().json(json!({ "ok": true }))
-}
\ No newline at end of file
+}
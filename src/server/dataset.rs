@@ -1,12 +1,44 @@
-use actix_web::{HttpRequest, HttpResponse};
-use serde_json::json;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde_json::Value;
 
-use crate::utils::secret::check_secret;
+use crate::engine::access::strip_hidden_columns;
+use crate::engine::dataset::demo_schema;
+use crate::server::auth::authenticate;
+use crate::utils::credentials::CredentialVerifier;
+use crate::utils::ids::request_id;
+use crate::utils::policies::RowPolicies;
 use crate::utils::sanitize::get_dataset;
-pub async fn handle_datasets(req: HttpRequest) -> HttpResponse {
-    if let Err(resp) = check_secret(&req) {
-        return resp;
-    }
+use crate::utils::signing::SigningConfig;
+use crate::utils::tenants::Tenants;
+pub async fn handle_datasets(
+    req: HttpRequest,
+    body: web::Bytes,
+    verifier: web::Data<dyn CredentialVerifier>,
+    tenants: web::Data<Tenants>,
+    policies: web::Data<RowPolicies>,
+    signing: web::Data<SigningConfig>,
+) -> HttpResponse {
+    let identity = match authenticate(&req, &body, verifier.get_ref(), &tenants, &signing) {
+        Ok(identity) => identity,
+        Err(resp) => return resp,
+    };
 
-    HttpResponse::Ok().json(json!([get_dataset()]))
-}
\ No newline at end of file
+    let schema = demo_schema();
+    let visible: Vec<Value> = vec![get_dataset()]
+        .into_iter()
+        .filter(|d| {
+            let id = d["id"].as_str().unwrap_or("");
+            tenants.can_access(&identity, id) && policies.filters_for(&identity, id).is_some()
+        })
+        .map(|d| strip_hidden_columns(d, &schema, &identity))
+        .collect();
+    log::info!(
+        request_id = request_id(&req).as_str(),
+        user = identity.user.as_str(),
+        tenant = identity.tenant.as_deref().unwrap_or(""),
+        datasets = visible.len();
+        "datasets listed"
+    );
+
+    HttpResponse::Ok().json(visible)
+}
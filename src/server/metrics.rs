@@ -0,0 +1,10 @@
+use actix_web::HttpResponse;
+
+use crate::utils::metrics::metrics;
+
+// Prometheus scrape endpoint.
+pub async fn handle_metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics().render())
+}
@@ -0,0 +1,165 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpResponse};
+use chrono::Utc;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::time::Instant;
+
+use crate::engine::dataset::is_known_dataset;
+use crate::errors::PluginError;
+use crate::utils::ids::{accept_request_id, make_req_id, RequestId};
+use crate::utils::metrics::metrics;
+use crate::utils::ratelimit::{RateLimiter, RowsReturned};
+use crate::utils::secret::secret_store;
+use crate::utils::signing::{signed_client, SigningConfig};
+use crate::utils::tenants::Tenants;
+
+// Request count and latency per route pattern, and errors by PluginError variant.
+pub async fn track_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let res = next.call(req).await?;
+
+    let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    metrics().record_request(&route, res.status().as_u16(), started.elapsed().as_secs_f64());
+    if let Some(err) = res.response().error().and_then(|e| e.as_error::<PluginError>()) {
+        metrics().record_error(err.kind());
+    }
+    Ok(res)
+}
+
+// Takes the caller's X-Request-Id (or makes one), makes it available to the
+// handlers and echoes it on every response, errors included. JSON error bodies
+// (the ErrorResponse shape) also get it as "request_id".
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let incoming = req.headers().get("X-Request-Id").and_then(|v| v.to_str().ok());
+    let rid = accept_request_id(incoming).unwrap_or_else(make_req_id);
+    req.extensions_mut().insert(RequestId(rid.clone()));
+
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&rid) {
+        res.headers_mut().insert(HeaderName::from_static("x-request-id"), value);
+    }
+
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let is_error = res.status().is_client_error() || res.status().is_server_error();
+    if !is_error || !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (http_req, http_res) = res.into_parts();
+    let (http_res, res_body) = http_res.into_parts();
+    let bytes = body::to_bytes(res_body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.into().to_string()))?;
+
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(mut err)) => {
+            err.insert("request_id".into(), Value::from(rid));
+            serde_json::to_vec(&err).map(Into::into).unwrap_or(bytes)
+        }
+        _ => bytes,
+    };
+    let http_res = http_res.set_body(BoxBody::new(bytes));
+    Ok(ServiceResponse::new(http_req, http_res))
+}
+
+// Who the limits apply to: the tenant owning the X-Secret, the shared secret
+// itself, or for signed requests the signing key (hashed, so raw secrets are not
+// kept around). Unknown secrets and bad signatures are not limited here;
+// authentication rejects them.
+fn rate_client(req: &ServiceRequest, body: &[u8]) -> Option<String> {
+    let signed = || {
+        let signing = req.app_data::<web::Data<SigningConfig>>()?;
+        signed_client(req.request(), body, signing)
+    };
+    let Some(secret) = req.headers().get("X-Secret").and_then(|v| v.to_str().ok()) else {
+        return signed();
+    };
+    if let Some(tenant) = req
+        .app_data::<web::Data<Tenants>>()
+        .and_then(|t| t.by_secret(secret).map(|t| t.id.clone()))
+    {
+        return Some(format!("tenant:{}", tenant));
+    }
+    if !secret_store().is_valid(secret, Utc::now()) {
+        return signed();
+    }
+    let hash: String = Sha256::digest(secret.as_bytes())
+        .iter()
+        .take(6)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Some(format!("secret:{}", hash))
+}
+
+// Only datasets that exist get a bucket; any other id in the body would
+// otherwise add one per request.
+fn dataset_in(body: &[u8]) -> Option<String> {
+    let body: Value = serde_json::from_slice(body).ok()?;
+    body.get("dataset_id")
+        .or_else(|| body.get("id"))
+        .and_then(Value::as_str)
+        .filter(|id| is_known_dataset(id))
+        .map(str::to_string)
+}
+
+// Token-bucket limits per client and per client and dataset, and the daily row
+// quota (rows counted from the RowsReturned the handlers leave on the response).
+// The limiter comes from the app data; without one nothing is limited.
+pub async fn rate_limit(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let limiter = req.app_data::<web::Data<RateLimiter>>().filter(|l| l.is_enabled()).cloned();
+    let Some(limiter) = limiter else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+
+    // the dataset and the signature need the body; put it back for the handler
+    let is_query = req.method() == Method::POST && req.path().starts_with("/query");
+    let is_signed = req.method() == Method::POST && req.headers().contains_key("X-Signature");
+    let body = if is_query || is_signed {
+        let bytes = req.extract::<web::Bytes>().await?;
+        req.set_payload(Payload::from(bytes.clone()));
+        bytes
+    } else {
+        web::Bytes::new()
+    };
+
+    let Some(client) = rate_client(&req, &body) else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+    let dataset_id = if is_query { dataset_in(&body) } else { None };
+
+    if let Err(e) = limiter.check(&client, dataset_id.as_deref(), Instant::now(), Utc::now()) {
+        log::warn!(
+            client = client.as_str(),
+            dataset_id = dataset_id.as_deref().unwrap_or(""),
+            path = req.path(),
+            error = e.to_string().as_str();
+            "rate limited"
+        );
+        return Ok(req.into_response(HttpResponse::from_error(e)));
+    }
+
+    let res = next.call(req).await?;
+    let rows = res.response().extensions().get::<RowsReturned>().map(|r| r.0);
+    if let Some(rows) = rows {
+        limiter.record_rows(&client, rows, Utc::now());
+    }
+    Ok(res.map_into_boxed_body())
+}
@@ -1,4 +1,8 @@
+pub mod auth;
 pub mod dataset;
 pub mod health;
+pub mod metrics;
+pub mod middleware;
 pub mod query;
+pub mod stream;
 pub mod authorize;
\ No newline at end of file
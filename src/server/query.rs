@@ -1,34 +1,58 @@
-use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder};
+use chrono::Utc;
+use serde_json::Value;
+use std::time::Instant;
 
-use crate::engine::execute::run;
+use crate::engine::cache::{cache_key, etag};
+use crate::engine::cancel::{timeout_for, CancelOnDrop, Cancellation};
+use crate::engine::context::QueryContext;
+use crate::engine::dataset::is_known_dataset;
+use crate::engine::execute::{run_or_stream, QueryResult};
+use crate::engine::explain::explain;
+use crate::engine::pool::query_pool;
+use crate::engine::stats::{elapsed_ms, QueryStats};
 use crate::errors::PluginError;
 use crate::luzmo::types::QueryRequest;
-use crate::utils::ids::make_req_id;
-use crate::utils::secret::check_secret;
+use crate::server::auth::authenticate;
+use crate::server::stream::{stream_min_rows, JsonArrayBody, StreamEnd};
+use crate::utils::credentials::CredentialVerifier;
+use crate::utils::ids::request_id;
+use crate::utils::metrics::metrics;
+use crate::utils::policies::RowPolicies;
+use crate::utils::ratelimit::RowsReturned;
+use crate::utils::redact::RedactConfig;
+use crate::utils::signing::SigningConfig;
+use crate::utils::slowlog::{slow_query_log, SlowQueryEntry, SlowQueryLog};
+use crate::utils::tenants::Tenants;
 
-// Main query handler
-pub async fn handle_query(
-    req: HttpRequest,
-    body: web::Bytes,
-) -> Result<HttpResponse, PluginError> {
-    let rid = make_req_id();
-
-    println!("[{}] ===== NEW QUERY REQUEST =====", rid);
-    println!("[{}] Headers: {:?}", rid, req.headers());
+// Shared by /query and /query/explain: authentication, body parsing and the
+// dataset check. Errors come back as ready responses (PluginErrors stay attached
+// for the metrics middleware).
+fn prepare(
+    req: &HttpRequest,
+    body: &web::Bytes,
+    verifier: &dyn CredentialVerifier,
+    tenants: &Tenants,
+    policies: &RowPolicies,
+    signing: &SigningConfig,
+    rid: &str,
+) -> Result<(QueryRequest, String, QueryContext), HttpResponse> {
+    let redact = RedactConfig::from_env();
+    log::info!(request_id = rid, path = req.path(), bytes = body.len(); "query request");
+    log::debug!(request_id = rid, headers = redact.headers(req.headers()).as_str(); "query headers");
 
-    if let Err(resp) = check_secret(&req) {
-        println!("[{}] ✗ Secret check failed", rid);
-        return Ok(resp);
-    }
+    let identity = authenticate(req, body, verifier, tenants, signing).inspect_err(|resp| {
+        log::warn!(request_id = rid, status = resp.status().as_u16(); "authentication failed");
+    })?;
 
-    let raw = String::from_utf8_lossy(&body);
-    println!("[{}] /query raw body:\n{}", rid, raw);
+    log::debug!(request_id = rid, body = redact.body(body).as_str(); "query body");
 
     let q: QueryRequest = serde_json::from_slice(body.as_ref()).map_err(|e| {
-        println!("[{}] Json deserialize error: {}", rid, e);
-        PluginError::InvalidRequest {
+        log::warn!(request_id = rid, error = e.to_string().as_str(); "json deserialize error");
+        HttpResponse::from_error(PluginError::InvalidRequest {
             message: format!("Json deserialize error: {}", e),
-        }
+        })
     })?;
 
     let dataset_id = q
@@ -37,14 +61,210 @@ pub async fn handle_query(
         .or_else(|| q.id.clone())
         .unwrap_or_default();
 
-    if dataset_id != "demo" {
-        return Err(PluginError::DatasetNotFound {
+    // datasets van een andere tenant, of zonder policy voor deze caller, bestaan niet
+    let row_filters = policies
+        .filters_for(&identity, &dataset_id)
+        .filter(|_| is_known_dataset(&dataset_id) && tenants.can_access(&identity, &dataset_id));
+    let Some(row_filters) = row_filters else {
+        return Err(HttpResponse::from_error(PluginError::DatasetNotFound {
             message: format!("Unknown dataset id: {}", dataset_id),
-        });
+        }));
+    };
+
+    let ctx = QueryContext {
+        row_filters,
+        identity,
+        request_id: rid.to_string(),
+        cancel: Cancellation::with_timeout(timeout_for(&dataset_id)),
+    };
+    Ok((q, dataset_id, ctx))
+}
+
+fn slow_entry(
+    slow_log: &SlowQueryLog,
+    body: &[u8],
+    q: &QueryRequest,
+    ctx: &QueryContext,
+    dataset_id: &str,
+    elapsed_ms: f64,
+    stats: &QueryStats,
+) -> SlowQueryEntry {
+    let (body, redacted) = slow_log.redact_body(&serde_json::from_slice(body).unwrap_or(Value::Null));
+    SlowQueryEntry {
+        ts: Utc::now().to_rfc3339(),
+        request_id: ctx.request_id.clone(),
+        dataset_id: dataset_id.to_string(),
+        user: ctx.identity.user.clone(),
+        tenant: ctx.identity.tenant.clone(),
+        roles: ctx.identity.roles.clone(),
+        elapsed_ms,
+        body,
+        redacted,
+        plan: explain(q, ctx).unwrap_or(Value::Null),
+        stats: stats.clone(),
+    }
+}
+
+// Blocking file I/O (rotation included): only on a blocking thread.
+fn write_slow_entry(slow_log: &SlowQueryLog, entry: &SlowQueryEntry) {
+    if let Err(e) = slow_log.record(entry) {
+        log::error!(request_id = entry.request_id.as_str(), error = e.as_str(); "could not write slow query log");
+    }
+}
+
+async fn record_slow_query(slow_log: &'static SlowQueryLog, entry: SlowQueryEntry) {
+    let rid = entry.request_id.clone();
+    if let Err(e) = web::block(move || write_slow_entry(slow_log, &entry)).await {
+        log::error!(request_id = rid.as_str(), error = e.to_string().as_str(); "could not write slow query log");
+    }
+}
+
+// Luzmo deviation: Luzmo only ever POSTs queries, so a matching tag on a POST
+// gets a 304 too. "*" is ignored. Tags are compared weakly (W/ or not).
+fn if_none_match(req: &HttpRequest, etag: &str) -> bool {
+    let opaque = |t: &str| t.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    req.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| opaque(t) == etag))
+}
+
+fn log_query_done(ctx: &QueryContext, dataset_id: &str, stats: &QueryStats, rows_out: usize, started: Instant) {
+    log::info!(
+        request_id = ctx.request_id.as_str(),
+        dataset_id = dataset_id,
+        user = ctx.identity.user.as_str(),
+        rows_scanned = stats.rows_scanned,
+        rows_out = rows_out,
+        elapsed_ms = started.elapsed().as_millis() as u64;
+        "query done"
+    );
+}
+
+const STREAM_CHUNK_BYTES: usize = 64 * 1024;
+
+// Luzmo expects a bare array, so the stats travel in a header.
+fn ok_response(q: &QueryRequest, etag: String, stats: &QueryStats) -> HttpResponseBuilder {
+    let mut resp = HttpResponse::Ok();
+    resp.content_type("application/json");
+    resp.insert_header((header::ETAG, etag));
+    resp.extensions_mut().insert(RowsReturned(stats.rows_returned));
+    if q.options.as_ref().is_some_and(|o| o.include_metadata) {
+        if let Ok(meta) = serde_json::to_string(stats) {
+            resp.insert_header(("X-Query-Metadata", meta));
+        }
     }
+    resp
+}
 
-    let rows = run(&q)?;
+// Main query handler
+pub async fn handle_query(
+    req: HttpRequest,
+    body: web::Bytes,
+    verifier: web::Data<dyn CredentialVerifier>,
+    tenants: web::Data<Tenants>,
+    policies: web::Data<RowPolicies>,
+    signing: web::Data<SigningConfig>,
+) -> Result<HttpResponse, PluginError> {
+    let started = Instant::now();
+    let rid = request_id(&req);
+    let (q, dataset_id, ctx) = match prepare(&req, &body, verifier.get_ref(), &tenants, &policies, &signing, &rid) {
+        Ok(prepared) => prepared,
+        Err(resp) => return Ok(resp),
+    };
 
-    println!("[{}] ✓ rows_out={}", rid, rows.len());
-    Ok(HttpResponse::Ok().json(rows))
-}
\ No newline at end of file
+    let etag = etag(&dataset_id, &cache_key(&q, &ctx));
+    if if_none_match(&req, &etag) {
+        log::info!(request_id = rid.as_str(), dataset_id = dataset_id.as_str(); "query not modified");
+        return Ok(HttpResponse::NotModified().insert_header((header::ETAG, etag)).finish());
+    }
+
+    // Off the async workers, within the pool's limits (waiting for a slot counts
+    // against the deadline). If the client disconnects, actix drops this future
+    // and the guard cancels the query at its next check.
+    let guard = CancelOnDrop::new(&ctx.cancel, &dataset_id);
+    let min_rows = stream_min_rows();
+    let cancel = ctx.cancel.clone();
+    let ((outcome, q, ctx), slot) = query_pool()
+        .run_holding(&cancel, move || (run_or_stream(&q, &ctx, min_rows), q, ctx))
+        .await
+        .inspect_err(|e| {
+            log::warn!(request_id = rid.as_str(), dataset_id = dataset_id.as_str(), error = e.to_string().as_str(); "query rejected");
+        })?;
+    guard.disarm();
+
+    let (result, mut stats) = outcome.inspect_err(|e| {
+        log::warn!(request_id = rid.as_str(), dataset_id = dataset_id.as_str(), error = e.to_string().as_str(); "query failed");
+    })?;
+    metrics().record_query(&dataset_id, &stats);
+
+    let rows = match result {
+        QueryResult::Rows(rows) => {
+            drop(slot);
+            rows
+        }
+        QueryResult::Stream(rows) => {
+            log::info!(
+                request_id = rid.as_str(),
+                dataset_id = dataset_id.as_str(),
+                user = ctx.identity.user.as_str(),
+                rows_out = rows.len(),
+                elapsed_ms = started.elapsed().as_millis() as u64;
+                "query streaming"
+            );
+            let mut resp = ok_response(&q, etag, &stats);
+            // The rows are produced on the pool, in the query's slot; the logs and
+            // the slow query log follow once the last row is out, like below.
+            let stream = JsonArrayBody::new(rows, STREAM_CHUNK_BYTES).holding(slot).on_finish(move |end| match end {
+                StreamEnd::Complete(rows_out) => {
+                    let total_ms = elapsed_ms(started);
+                    if let Some(slow_log) = slow_query_log().filter(|l| l.is_slow(total_ms)) {
+                        // al op een blocking thread
+                        write_slow_entry(slow_log, &slow_entry(slow_log, &body, &q, &ctx, &dataset_id, total_ms, &stats));
+                    }
+                    log_query_done(&ctx, &dataset_id, &stats, rows_out, started);
+                }
+                StreamEnd::Failed(e) => {
+                    log::warn!(request_id = rid.as_str(), dataset_id = dataset_id.as_str(), error = e.to_string().as_str(); "query failed");
+                }
+                StreamEnd::Disconnected { sent } => {
+                    metrics().record_cancelled(&dataset_id);
+                    log::info!(request_id = rid.as_str(), dataset_id = dataset_id.as_str(), rows_out = sent; "client disconnected during the stream");
+                }
+            });
+            return Ok(resp.body(stream));
+        }
+    };
+
+    let phase = Instant::now();
+    let payload = serde_json::to_vec(rows.as_ref()).map_err(|e| PluginError::InternalError {
+        message: format!("Could not serialize rows: {}", e),
+    })?;
+    stats.timings.serialize_ms = elapsed_ms(phase);
+
+    let total_ms = elapsed_ms(started);
+    if let Some(slow_log) = slow_query_log().filter(|l| l.is_slow(total_ms)) {
+        record_slow_query(slow_log, slow_entry(slow_log, &body, &q, &ctx, &dataset_id, total_ms, &stats)).await;
+    }
+    log_query_done(&ctx, &dataset_id, &stats, rows.len(), started);
+
+    Ok(ok_response(&q, etag, &stats).body(payload))
+}
+
+// Resolved plan for a query, without executing it.
+pub async fn handle_explain(
+    req: HttpRequest,
+    body: web::Bytes,
+    verifier: web::Data<dyn CredentialVerifier>,
+    tenants: web::Data<Tenants>,
+    policies: web::Data<RowPolicies>,
+    signing: web::Data<SigningConfig>,
+) -> Result<HttpResponse, PluginError> {
+    let rid = request_id(&req);
+    let (q, _, ctx) = match prepare(&req, &body, verifier.get_ref(), &tenants, &policies, &signing, &rid) {
+        Ok(prepared) => prepared,
+        Err(resp) => return Ok(resp),
+    };
+    Ok(HttpResponse::Ok().json(explain(&q, &ctx)?))
+}
@@ -0,0 +1,196 @@
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::web::Bytes;
+use std::env;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+use crate::engine::pool::Slot;
+use crate::engine::rows::RawRows;
+use crate::errors::PluginError;
+
+// Raw results with at least this many rows are streamed
+// (LUZMO_PLUGIN_STREAM_MIN_ROWS, 0 = never).
+pub fn stream_min_rows() -> usize {
+    match env::var("LUZMO_PLUGIN_STREAM_MIN_ROWS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10_000)
+    {
+        0 => usize::MAX,
+        n => n,
+    }
+}
+
+// How a stream ended.
+#[derive(Debug)]
+pub enum StreamEnd<'a> {
+    // Every row was sent.
+    Complete(usize),
+    // The deadline, a cancel or a serialization error cut it short.
+    Failed(&'a PluginError),
+    // The body was dropped before the end, e.g. the client went away.
+    Disconnected { sent: usize },
+}
+
+// Called once, with how the stream ended. Runs on the blocking thread that
+// produced the rows, or where the body is dropped.
+pub type OnFinish = Box<dyn FnOnce(StreamEnd<'_>) + Send>;
+
+// Chunks buffered between the producing thread and the connection.
+const CHUNKS_AHEAD: usize = 2;
+
+// Writes the rows as a JSON array, in chunks of about `chunk_bytes`.
+struct Producer {
+    rows: RawRows,
+    chunk_bytes: usize,
+    started: bool,
+    done: bool,
+    sent: usize,
+    on_finish: Option<OnFinish>,
+}
+
+impl Producer {
+    fn finish(&mut self, result: StreamEnd<'_>) {
+        self.done = true;
+        if let Some(f) = self.on_finish.take() {
+            f(result);
+        }
+    }
+
+    fn next_chunk(&mut self) -> Option<Result<Bytes, PluginError>> {
+        if self.done {
+            return None;
+        }
+
+        let mut buf = Vec::with_capacity(self.chunk_bytes + 256);
+        if !self.started {
+            buf.push(b'[');
+            self.started = true;
+        }
+        while buf.len() < self.chunk_bytes {
+            let row = match self.rows.next() {
+                Some(Ok(row)) => row,
+                Some(Err(e)) => {
+                    // het array is al half verstuurd: de verbinding afbreken
+                    self.finish(StreamEnd::Failed(&e));
+                    return Some(Err(e));
+                }
+                None => {
+                    buf.push(b']');
+                    let sent = self.sent;
+                    self.finish(StreamEnd::Complete(sent));
+                    break;
+                }
+            };
+            if self.sent > 0 {
+                buf.push(b',');
+            }
+            if let Err(e) = serde_json::to_writer(&mut buf, &row) {
+                let e = PluginError::InternalError {
+                    message: format!("Could not serialize rows: {}", e),
+                };
+                self.finish(StreamEnd::Failed(&e));
+                return Some(Err(e));
+            }
+            self.sent += 1;
+        }
+        Some(Ok(Bytes::from(buf)))
+    }
+
+    // On a blocking thread: produce until done or until the body is gone.
+    fn run(mut self: Box<Self>, tx: mpsc::Sender<Result<Bytes, PluginError>>, _slot: Option<Slot>) {
+        while let Some(chunk) = self.next_chunk() {
+            if tx.blocking_send(chunk).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for Producer {
+    fn drop(&mut self) {
+        if self.on_finish.is_some() {
+            let sent = self.sent;
+            self.finish(StreamEnd::Disconnected { sent });
+        }
+    }
+}
+
+enum State {
+    Idle(Box<Producer>, Option<Slot>),
+    Running(mpsc::Receiver<Result<Bytes, PluginError>>),
+    Done,
+}
+
+// A JSON array sent with chunked encoding while the rows are produced. Rows are
+// projected and serialized on the blocking thread pool (holding the query's
+// pool slot, if given) from the first poll on; only a couple of chunks are in
+// memory at a time.
+pub struct JsonArrayBody {
+    state: State,
+}
+
+impl JsonArrayBody {
+    pub fn new(rows: RawRows, chunk_bytes: usize) -> Self {
+        JsonArrayBody {
+            state: State::Idle(
+                Box::new(Producer {
+                    rows,
+                    chunk_bytes,
+                    started: false,
+                    done: false,
+                    sent: 0,
+                    on_finish: None,
+                }),
+                None,
+            ),
+        }
+    }
+
+    pub fn on_finish(mut self, f: impl FnOnce(StreamEnd<'_>) + Send + 'static) -> Self {
+        if let State::Idle(producer, _) = &mut self.state {
+            producer.on_finish = Some(Box::new(f));
+        }
+        self
+    }
+
+    // Keeps the query's pool slot until the last chunk is produced.
+    pub fn holding(mut self, slot: Slot) -> Self {
+        if let State::Idle(_, held) = &mut self.state {
+            *held = Some(slot);
+        }
+        self
+    }
+}
+
+impl MessageBody for JsonArrayBody {
+    type Error = PluginError;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        this.state = match std::mem::replace(&mut this.state, State::Done) {
+            State::Idle(producer, slot) => {
+                let (tx, rx) = mpsc::channel(CHUNKS_AHEAD);
+                actix_web::rt::task::spawn_blocking(move || producer.run(tx, slot));
+                State::Running(rx)
+            }
+            state => state,
+        };
+
+        let State::Running(rx) = &mut this.state else {
+            return Poll::Ready(None);
+        };
+        match rx.poll_recv(cx) {
+            Poll::Ready(None) => {
+                this.state = State::Done;
+                Poll::Ready(None)
+            }
+            other => other,
+        }
+    }
+}
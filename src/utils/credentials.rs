@@ -0,0 +1,157 @@
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+
+use crate::utils::secret::{constant_time_eq, record_auth_failure};
+
+// Who is calling: resolved from the per-connection X-Key / X-Token / X-Host
+// headers Luzmo forwards with every request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Identity {
+    pub user: String,
+    pub tenant: Option<String>,
+    pub roles: Vec<String>,
+}
+
+impl Identity {
+    pub fn anonymous() -> Self {
+        Identity {
+            user: "anonymous".to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub key: Option<String>,
+    pub token: Option<String>,
+    pub host: Option<String>,
+}
+
+impl Credentials {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        Credentials {
+            key: header("X-Key"),
+            token: header("X-Token"),
+            host: header("X-Host"),
+        }
+    }
+}
+
+pub trait CredentialVerifier: Send + Sync {
+    // Err carries the reason, it is returned to the caller as the 401 message.
+    fn verify(&self, creds: &Credentials) -> Result<Identity, String>;
+}
+
+// Default when no credentials are configured: everyone is accepted, the X-Key
+// (if any) is used as the user name.
+pub struct AllowAll;
+
+impl CredentialVerifier for AllowAll {
+    fn verify(&self, creds: &Credentials) -> Result<Identity, String> {
+        Ok(match &creds.key {
+            Some(key) => Identity {
+                user: key.clone(),
+                ..Default::default()
+            },
+            None => Identity::anonymous(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CredentialEntry {
+    pub key: String,
+    pub token: String,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub host: Option<String>,
+}
+
+// Fixed key/token pairs, e.g. loaded from LUZMO_PLUGIN_CREDENTIALS_FILE.
+pub struct StaticCredentials {
+    entries: HashMap<String, CredentialEntry>,
+}
+
+impl StaticCredentials {
+    pub fn new(entries: Vec<CredentialEntry>) -> Self {
+        StaticCredentials {
+            entries: entries.into_iter().map(|e| (e.key.clone(), e)).collect(),
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let entries: Vec<CredentialEntry> =
+            serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(StaticCredentials::new(entries))
+    }
+}
+
+impl CredentialVerifier for StaticCredentials {
+    fn verify(&self, creds: &Credentials) -> Result<Identity, String> {
+        let (Some(key), Some(token)) = (&creds.key, &creds.token) else {
+            return Err("Missing X-Key or X-Token".to_string());
+        };
+        let entry = self
+            .entries
+            .get(key)
+            .filter(|e| constant_time_eq(e.token.as_bytes(), token.as_bytes()))
+            .ok_or_else(|| "Invalid X-Key or X-Token".to_string())?;
+
+        if let Some(host) = &entry.host {
+            if creds.host.as_deref() != Some(host.as_str()) {
+                return Err("X-Host not allowed for this key".to_string());
+            }
+        }
+
+        Ok(Identity {
+            user: entry.user.clone().unwrap_or_else(|| entry.key.clone()),
+            tenant: entry.tenant.clone(),
+            roles: entry.roles.clone(),
+        })
+    }
+}
+
+// Whether callers are verified at all; without it the X-Key is taken at face value.
+pub fn credentials_configured() -> bool {
+    env::var("LUZMO_PLUGIN_CREDENTIALS_FILE").is_ok()
+}
+
+// An unreadable or invalid credentials file is an error, not an empty list:
+// the server refuses to start rather than reject (or accept) everyone.
+pub fn verifier_from_env() -> Result<Arc<dyn CredentialVerifier>, String> {
+    match env::var("LUZMO_PLUGIN_CREDENTIALS_FILE") {
+        Ok(path) => Ok(Arc::new(StaticCredentials::from_file(&path)?)),
+        Err(_) => Ok(Arc::new(AllowAll)),
+    }
+}
+
+pub fn identify(req: &HttpRequest, verifier: &dyn CredentialVerifier) -> Result<Identity, HttpResponse> {
+    verifier
+        .verify(&Credentials::from_request(req))
+        .map_err(|reason| {
+            record_auth_failure(req, &reason);
+            HttpResponse::Unauthorized().json(json!({
+                "type": { "code": 401, "description": "Unauthorized" },
+                "message": reason
+            }))
+        })
+}
@@ -1,9 +1,72 @@
+use actix_web::{HttpMessage, HttpRequest};
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const RANDOM_MASK: u128 = (1 << 80) - 1;
+
+// ULID layout: 48 bits of unix milliseconds + 80 random bits, as 26 Crockford
+// base32 characters. Ids sort by time; within the same millisecond the random
+// part is incremented instead of redrawn, so they stay unique and ordered.
 pub fn make_req_id() -> String {
+    static LAST: Mutex<(u64, u128)> = Mutex::new((0, 0));
+
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_millis();
-    format!("{:x}", now)
-}
\ No newline at end of file
+        .as_millis() as u64;
+
+    let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner());
+    let (ms, rand) = if now <= last.0 {
+        // zelfde ms (of klok teruggezet): doortellen op de vorige
+        let next = (last.1 + 1) & RANDOM_MASK;
+        (if next == 0 { last.0 + 1 } else { last.0 }, next)
+    } else {
+        (now, random_bits() & RANDOM_MASK)
+    };
+    *last = (ms, rand);
+
+    encode_ulid(ms, rand)
+}
+
+pub(crate) fn random_bits() -> u128 {
+    // RandomState is seeded per instance from the OS, good enough for ids
+    let half = || {
+        let mut h = RandomState::new().build_hasher();
+        h.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+        h.finish() as u128
+    };
+    (half() << 64) | half()
+}
+
+pub fn encode_ulid(ms: u64, rand: u128) -> String {
+    let value = ((ms as u128 & 0xFFFF_FFFF_FFFF) << 80) | (rand & RANDOM_MASK);
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+// The id of the current request, stored in the request extensions by the
+// request-id middleware.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+// An X-Request-Id from the caller is reused as long as it is a sane token;
+// anything else could be used to inject into the logs.
+pub fn accept_request_id(incoming: Option<&str>) -> Option<String> {
+    incoming
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .filter(|id| id.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c)))
+        .map(str::to_string)
+}
+
+pub fn request_id(req: &HttpRequest) -> String {
+    if let Some(RequestId(id)) = req.extensions().get::<RequestId>() {
+        return id.clone();
+    }
+    let incoming = req.headers().get("X-Request-Id").and_then(|v| v.to_str().ok());
+    accept_request_id(incoming).unwrap_or_else(make_req_id)
+}
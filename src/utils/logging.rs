@@ -0,0 +1,47 @@
+use chrono::{SecondsFormat, Utc};
+use log::kv::{Key, Value as KvValue, VisitSource};
+use serde_json::{Map, Value};
+use std::io::Write;
+
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: KvValue<'kvs>) -> Result<(), log::kv::Error> {
+        let v = if let Some(b) = value.to_bool() {
+            Value::from(b)
+        } else if let Some(n) = value.to_u64() {
+            Value::from(n)
+        } else if let Some(n) = value.to_i64() {
+            Value::from(n)
+        } else if let Some(n) = value.to_f64() {
+            Value::from(n)
+        } else {
+            Value::from(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), v);
+        Ok(())
+    }
+}
+
+// One log line as a JSON object: ts, level, target, msg and the record's
+// key-values as fields, e.g.
+//   log::info!(request_id = rid.as_str(), rows_out = n; "query done");
+pub fn json_line(record: &log::Record) -> Value {
+    let mut line = Map::new();
+    line.insert(
+        "ts".into(),
+        Value::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+    );
+    line.insert("level".into(), Value::from(record.level().as_str()));
+    line.insert("target".into(), Value::from(record.target()));
+    line.insert("msg".into(), Value::from(record.args().to_string()));
+    let _ = record.key_values().visit(&mut Fields(&mut line));
+    Value::Object(line)
+}
+
+// JSON lines on stderr, level from RUST_LOG (default info).
+pub fn init() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format(|buf, record| writeln!(buf, "{}", json_line(record)))
+        .init();
+}
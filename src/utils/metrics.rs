@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+use crate::engine::stats::QueryStats;
+
+type Labels = Vec<(String, String)>;
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// name, type, help — rendered in this order.
+const METRICS: &[(&str, &str, &str)] = &[
+    ("luzmo_plugin_http_requests_total", "counter", "HTTP requests by route and status."),
+    ("luzmo_plugin_http_request_duration_seconds", "histogram", "HTTP request latency by route."),
+    ("luzmo_plugin_queries_total", "counter", "Executed queries by dataset."),
+    ("luzmo_plugin_rows_scanned_total", "counter", "Rows read from the dataset by queries."),
+    ("luzmo_plugin_rows_returned_total", "counter", "Rows returned by queries."),
+    ("luzmo_plugin_groups_total", "counter", "Groups produced by aggregation queries."),
+    ("luzmo_plugin_cache_hits_total", "counter", "Queries answered from the result cache."),
+    ("luzmo_plugin_queries_cancelled_total", "counter", "Queries cancelled by dataset because the client went away."),
+    ("luzmo_plugin_auth_failures_total", "counter", "Failed authentication attempts."),
+    ("luzmo_plugin_errors_total", "counter", "Error responses by PluginError variant."),
+];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            counts: vec![0; LATENCY_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (i, le) in LATENCY_BUCKETS.iter().enumerate() {
+            if value <= *le {
+                self.counts[i] += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    counters: BTreeMap<(String, Labels), f64>,
+    histograms: BTreeMap<(String, Labels), Histogram>,
+}
+
+// In-process metrics in the Prometheus text format; no external registry.
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Registry>,
+}
+
+fn labels(pairs: &[(&str, &str)]) -> Labels {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+fn escape(v: &str) -> String {
+    v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn render_labels(labels: &Labels, extra: Option<(&str, &str)>) -> String {
+    let parts: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .chain(extra)
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape(v)))
+        .collect();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", parts.join(","))
+    }
+}
+
+impl Metrics {
+    pub fn inc(&self, name: &str, pairs: &[(&str, &str)], by: f64) {
+        if let Ok(mut reg) = self.inner.lock() {
+            *reg.counters.entry((name.to_string(), labels(pairs))).or_insert(0.0) += by;
+        }
+    }
+
+    pub fn observe(&self, name: &str, pairs: &[(&str, &str)], value: f64) {
+        if let Ok(mut reg) = self.inner.lock() {
+            reg.histograms
+                .entry((name.to_string(), labels(pairs)))
+                .or_insert_with(Histogram::new)
+                .observe(value);
+        }
+    }
+
+    pub fn record_request(&self, route: &str, status: u16, seconds: f64) {
+        let status = status.to_string();
+        self.inc("luzmo_plugin_http_requests_total", &[("route", route), ("status", &status)], 1.0);
+        self.observe("luzmo_plugin_http_request_duration_seconds", &[("route", route)], seconds);
+    }
+
+    pub fn record_query(&self, dataset_id: &str, stats: &QueryStats) {
+        let l = [("dataset", dataset_id)];
+        self.inc("luzmo_plugin_queries_total", &l, 1.0);
+        self.inc("luzmo_plugin_rows_returned_total", &l, stats.rows_returned as f64);
+        if stats.cache_hit {
+            // nothing was scanned or grouped this time
+            self.inc("luzmo_plugin_cache_hits_total", &l, 1.0);
+            return;
+        }
+        self.inc("luzmo_plugin_rows_scanned_total", &l, stats.rows_scanned as f64);
+        self.inc("luzmo_plugin_groups_total", &l, stats.groups as f64);
+    }
+
+    pub fn record_cancelled(&self, dataset_id: &str) {
+        self.inc("luzmo_plugin_queries_cancelled_total", &[("dataset", dataset_id)], 1.0);
+    }
+
+    pub fn record_auth_failure(&self) {
+        self.inc("luzmo_plugin_auth_failures_total", &[], 1.0);
+    }
+
+    pub fn record_error(&self, kind: &str) {
+        self.inc("luzmo_plugin_errors_total", &[("variant", kind)], 1.0);
+    }
+
+    pub fn render(&self) -> String {
+        let Ok(reg) = self.inner.lock() else {
+            return String::new();
+        };
+        let mut out = String::new();
+
+        for (name, kind, help) in METRICS {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+            for ((n, l), v) in reg.counters.iter().filter(|((n, _), _)| n == name) {
+                let _ = writeln!(out, "{}{} {}", n, render_labels(l, None), v);
+            }
+            for ((n, l), h) in reg.histograms.iter().filter(|((n, _), _)| n == name) {
+                for (le, c) in LATENCY_BUCKETS.iter().zip(&h.counts) {
+                    let le = le.to_string();
+                    let _ = writeln!(out, "{}_bucket{} {}", n, render_labels(l, Some(("le", &le))), c);
+                }
+                let _ = writeln!(out, "{}_bucket{} {}", n, render_labels(l, Some(("le", "+Inf"))), h.count);
+                let _ = writeln!(out, "{}_sum{} {}", n, render_labels(l, None), h.sum);
+                let _ = writeln!(out, "{}_count{} {}", n, render_labels(l, None), h.count);
+            }
+        }
+        out
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS_REGISTRY: OnceLock<Metrics> = OnceLock::new();
+    METRICS_REGISTRY.get_or_init(Metrics::default)
+}
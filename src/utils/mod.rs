@@ -1,3 +1,12 @@
+pub mod credentials;
 pub mod ids;
+pub mod logging;
+pub mod metrics;
+pub mod policies;
+pub mod ratelimit;
+pub mod redact;
 pub mod sanitize;
 pub mod secret;
+pub mod signing;
+pub mod slowlog;
+pub mod tenants;
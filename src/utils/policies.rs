@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use std::env;
+
+use crate::engine::filters::{normalize_op, resolve_column_id};
+use crate::luzmo::types::FilterExpr;
+use crate::utils::credentials::Identity;
+
+// Row-level security, loaded from LUZMO_PLUGIN_POLICIES_FILE:
+//   [{ "user": "alice", "dataset": "demo",
+//      "filters": [{ "column_id": "category", "expression": "in", "value": ["A", "B"] }] }]
+// Every selector that is set (user, tenant, role, dataset) must match. The filters of
+// all matching policies are ANDed with the request's own filters, so a request can
+// only narrow what a policy allows. Once policies are loaded, a caller that no
+// policy matches gets no rows at all; a policy with no filters grants full access.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RowPolicy {
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub dataset: Option<String>,
+    pub filters: Vec<FilterExpr>,
+}
+
+impl RowPolicy {
+    fn applies_to(&self, identity: &Identity, dataset_id: &str) -> bool {
+        self.user.as_deref().is_none_or(|u| u == identity.user)
+            && self.tenant.as_deref().is_none_or(|t| identity.tenant.as_deref() == Some(t))
+            && self.role.as_deref().is_none_or(|r| identity.roles.iter().any(|x| x == r))
+            && self.dataset.as_deref().is_none_or(|d| d == dataset_id)
+    }
+}
+
+// Default: no policies configured, every caller sees every row.
+#[derive(Debug, Clone, Default)]
+pub struct RowPolicies {
+    policies: Vec<RowPolicy>,
+    configured: bool,
+}
+
+// Filters a policy cannot be enforced with are refused at load time rather
+// than dropped at query time.
+fn validate_filter(f: &FilterExpr) -> Result<(), String> {
+    let col = resolve_column_id(f).unwrap_or_default();
+    match normalize_op(f.expression.as_deref()) {
+        None => Err(format!("policy filter on '{}' has no expression", col)),
+        Some("is not null") => Ok(()),
+        Some(op) if f.value.is_none() => Err(format!("policy filter '{}' on '{}' has no value", op, col)),
+        Some(_) => Ok(()),
+    }
+}
+
+impl RowPolicies {
+    pub fn new(policies: Vec<RowPolicy>) -> Self {
+        RowPolicies {
+            policies,
+            configured: true,
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let policies: Vec<RowPolicy> = serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path, e))?;
+        for f in policies.iter().flat_map(|p| &p.filters) {
+            validate_filter(f).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(RowPolicies::new(policies))
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.configured
+    }
+
+    // Een kapotte policy file mag niet stilletjes alles openzetten: dan faalt de server bij opstart.
+    pub fn from_env() -> Result<Self, String> {
+        match env::var("LUZMO_PLUGIN_POLICIES_FILE") {
+            Ok(path) => RowPolicies::from_file(&path),
+            Err(_) => Ok(RowPolicies::default()),
+        }
+    }
+
+    // None: policies are configured and none applies, the caller may not read the dataset.
+    pub fn filters_for(&self, identity: &Identity, dataset_id: &str) -> Option<Vec<FilterExpr>> {
+        let matching: Vec<&RowPolicy> = self
+            .policies
+            .iter()
+            .filter(|p| p.applies_to(identity, dataset_id))
+            .collect();
+        if self.configured && matching.is_empty() {
+            return None;
+        }
+        Some(matching.into_iter().flat_map(|p| p.filters.iter().cloned()).collect())
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::PluginError;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BucketConfig {
+    pub per_sec: f64,
+    pub burst: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    // per client (tenant or secret)
+    pub client: Option<BucketConfig>,
+    // per client and dataset
+    pub dataset: Option<BucketConfig>,
+    // rows returned per client per UTC day
+    pub daily_rows: Option<u64>,
+}
+
+impl RateLimitConfig {
+    // Everything is off unless configured; a rate of 0 also means off.
+    pub fn from_env() -> Self {
+        let num = |name: &str| env::var(name).ok().and_then(|s| s.parse::<f64>().ok());
+        let bucket = |rate: &str, burst: &str| {
+            num(rate).filter(|r| *r > 0.0).map(|per_sec| BucketConfig {
+                per_sec,
+                burst: num(burst).unwrap_or(per_sec * 2.0).max(1.0),
+            })
+        };
+        RateLimitConfig {
+            client: bucket("LUZMO_PLUGIN_RATE_LIMIT_RPS", "LUZMO_PLUGIN_RATE_LIMIT_BURST"),
+            dataset: bucket(
+                "LUZMO_PLUGIN_DATASET_RATE_LIMIT_RPS",
+                "LUZMO_PLUGIN_DATASET_RATE_LIMIT_BURST",
+            ),
+            daily_rows: num("LUZMO_PLUGIN_DAILY_ROW_QUOTA")
+                .filter(|n| *n > 0.0)
+                .map(|n| n as u64),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    // from here on the bucket is full again, same as a fresh one
+    full_at: Instant,
+}
+
+impl Bucket {
+    // Takes a token, or says how long until one is available.
+    fn take(&mut self, config: BucketConfig, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.per_sec).min(config.burst);
+        self.updated = now;
+        let taken = if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / config.per_sec))
+        };
+        self.full_at = now + Duration::from_secs_f64((config.burst - self.tokens) / config.per_sec);
+        taken
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    pruned_at: Option<Instant>,
+}
+
+// Buckets that have refilled are dropped at most this often, on the next request.
+const PRUNE_EVERY: Duration = Duration::from_secs(10);
+
+// Rows returned per client, for the current UTC day only.
+#[derive(Default)]
+struct DailyRows {
+    day: Option<NaiveDate>,
+    by_client: HashMap<String, u64>,
+}
+
+// Set by handlers on the response so the middleware can count rows against the quota.
+#[derive(Debug, Clone, Copy)]
+pub struct RowsReturned(pub usize);
+
+fn limited(message: String, wait: Duration) -> PluginError {
+    PluginError::RateLimited {
+        message,
+        retry_after: wait.as_secs_f64().ceil().max(1.0) as u64,
+    }
+}
+
+fn until_midnight(now: DateTime<Utc>) -> Duration {
+    let midnight = now
+        .date_naive()
+        .checked_add_days(Days::new(1))
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|t| t.and_utc());
+    midnight
+        .and_then(|m| (m - now).to_std().ok())
+        .unwrap_or(Duration::from_secs(1))
+}
+
+// Token buckets and daily row counters, keyed by client (see
+// server::middleware::rate_limit). Time is passed in so tests can drive it.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Buckets>,
+    rows: Mutex<DailyRows>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            buckets: Mutex::new(Buckets::default()),
+            rows: Mutex::new(DailyRows::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.client.is_some() || self.config.dataset.is_some() || self.config.daily_rows.is_some()
+    }
+
+    fn take(&self, key: String, config: BucketConfig, now: Instant) -> Result<(), Duration> {
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+        if buckets.pruned_at.is_none_or(|t| now.duration_since(t) >= PRUNE_EVERY) {
+            buckets.by_key.retain(|_, b| b.full_at > now);
+            buckets.pruned_at = Some(now);
+        }
+        buckets
+            .by_key
+            .entry(key)
+            .or_insert(Bucket {
+                tokens: config.burst,
+                updated: now,
+                full_at: now,
+            })
+            .take(config, now)
+    }
+
+    // Number of buckets kept (client and dataset buckets together).
+    pub fn len(&self) -> usize {
+        self.buckets.lock().map(|b| b.by_key.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn rows_used(&self, client: &str, today: NaiveDate) -> u64 {
+        self.rows
+            .lock()
+            .ok()
+            .filter(|r| r.day == Some(today))
+            .and_then(|r| r.by_client.get(client).copied())
+            .unwrap_or(0)
+    }
+
+    pub fn check(
+        &self,
+        client: &str,
+        dataset_id: Option<&str>,
+        now: Instant,
+        utc: DateTime<Utc>,
+    ) -> Result<(), PluginError> {
+        if let Some(quota) = self.config.daily_rows {
+            if self.rows_used(client, utc.date_naive()) >= quota {
+                return Err(limited(
+                    format!("Daily quota of {} rows used up", quota),
+                    until_midnight(utc),
+                ));
+            }
+        }
+        if let Some(config) = self.config.client {
+            self.take(format!("client:{}", client), config, now)
+                .map_err(|wait| limited("Rate limit exceeded".to_string(), wait))?;
+        }
+        if let (Some(config), Some(dataset_id)) = (self.config.dataset, dataset_id) {
+            self.take(format!("dataset:{}:{}", client, dataset_id), config, now)
+                .map_err(|wait| limited(format!("Rate limit exceeded for dataset {}", dataset_id), wait))?;
+        }
+        Ok(())
+    }
+
+    pub fn record_rows(&self, client: &str, rows: usize, utc: DateTime<Utc>) {
+        if self.config.daily_rows.is_none() {
+            return;
+        }
+        let today = utc.date_naive();
+        if let Ok(mut used) = self.rows.lock() {
+            // a new day starts every client at zero, so yesterday's counters can go
+            if used.day != Some(today) {
+                used.day = Some(today);
+                used.by_client.clear();
+            }
+            *used.by_client.entry(client.to_string()).or_insert(0) += rows as u64;
+        }
+    }
+}
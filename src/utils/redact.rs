@@ -0,0 +1,118 @@
+use actix_web::http::header::HeaderMap;
+use serde_json::Value;
+use std::env;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+// Never logged as-is, whatever the configuration says.
+const AUTH_HEADERS: &[&str] = &[
+    "x-secret",
+    "x-token",
+    "x-signature",
+    "authorization",
+    "cookie",
+    "set-cookie",
+];
+
+// What to hide from request logs. Body fields are matched case-insensitively on
+// their key, at any depth (LUZMO_PLUGIN_LOG_REDACT_FIELDS, comma separated).
+#[derive(Debug, Clone)]
+pub struct RedactConfig {
+    pub fields: Vec<String>,
+    pub max_body: usize,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        RedactConfig {
+            fields: vec!["password".into(), "secret".into(), "token".into()],
+            max_body: 2048,
+        }
+    }
+}
+
+impl RedactConfig {
+    pub fn from_env() -> Self {
+        let mut cfg = RedactConfig::default();
+        if let Ok(fields) = env::var("LUZMO_PLUGIN_LOG_REDACT_FIELDS") {
+            cfg.fields.extend(
+                fields
+                    .split(',')
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty()),
+            );
+        }
+        if let Some(n) = env::var("LUZMO_PLUGIN_LOG_MAX_BODY").ok().and_then(|s| s.parse().ok()) {
+            cfg.max_body = n;
+        }
+        cfg
+    }
+
+    pub fn headers(&self, headers: &HeaderMap) -> String {
+        let mut out: Vec<String> = headers
+            .iter()
+            .map(|(name, value)| {
+                let n = name.as_str();
+                let v = if AUTH_HEADERS.contains(&n) || self.is_sensitive(n) {
+                    REDACTED
+                } else {
+                    value.to_str().unwrap_or("<binary>")
+                };
+                format!("{}: {}", n, v)
+            })
+            .collect();
+        out.sort();
+        out.join(", ")
+    }
+
+    // JSON bodies get their sensitive fields replaced, anything else is only truncated.
+    pub fn body(&self, body: &[u8]) -> String {
+        let text = match serde_json::from_slice::<Value>(body) {
+            Ok(mut v) => {
+                self.redact_value(&mut v);
+                v.to_string()
+            }
+            Err(_) => String::from_utf8_lossy(body).into_owned(),
+        };
+        truncate(&text, self.max_body)
+    }
+
+    // Redacted copy of a JSON value, not truncated (for the slow query log).
+    pub fn value(&self, v: &Value) -> Value {
+        let mut v = v.clone();
+        self.redact_value(&mut v);
+        v
+    }
+
+    fn is_sensitive(&self, key: &str) -> bool {
+        self.fields.iter().any(|f| f.eq_ignore_ascii_case(key))
+    }
+
+    fn redact_value(&self, v: &mut Value) {
+        match v {
+            Value::Object(map) => {
+                for (k, val) in map.iter_mut() {
+                    if self.is_sensitive(k) {
+                        *val = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact_value(val);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|i| self.redact_value(i)),
+            _ => {}
+        }
+    }
+}
+
+pub fn truncate(s: &str, max: usize) -> String {
+    if s.len() <= max {
+        return s.to_string();
+    }
+    // niet midden in een utf-8 teken knippen
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} bytes truncated)", &s[..end], s.len() - end)
+}
@@ -67,6 +67,16 @@ pub fn get_dataset() -> serde_json::Value {
             "aggregable": true,
             "format": "0.00"
           }
+        },
+        {
+          "id": "customer",
+          "name": { "en": "Customer" },
+          "type": "hierarchy",
+          "properties": {
+            "display_name": { "en": "Customer" },
+            "filterable": true,
+            "groupable": true
+          }
         }
       ]
     })
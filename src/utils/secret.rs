@@ -1,21 +1,386 @@
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde_json::json;
+use std::collections::HashMap;
 use std::env;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
 
-pub fn expected_secret() -> String {
-    // In production: moet gezet zijn
-    // In development: fallback ok
-    env::var("LUZMO_PLUGIN_SECRET").unwrap_or_else(|_| {
-        let mode = env::var("NODE_ENV").unwrap_or_else(|_| "development".to_string());
-        if mode == "development" {
-            "dev_secret".to_string()
-        } else {
-            // geen panic: liever duidelijke 401/500 flow
-            "NOT_SET".to_string()
+use crate::utils::ids::request_id;
+use crate::utils::metrics::metrics;
+
+// Compares without an early exit, so the time taken does not reveal how many
+// leading bytes of a guess were right. Only the length can leak.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        diff |= (x ^ y) as usize;
+    }
+    diff == 0
+}
+
+// A valid X-Secret, optionally only until `expires_at` (used for the previous
+// secret while Luzmo is being switched over to the new one).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecretEntry {
+    pub value: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl SecretEntry {
+    pub fn new(value: &str) -> Self {
+        SecretEntry {
+            value: value.to_string(),
+            expires_at: None,
+        }
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|exp| now < exp)
+    }
+}
+
+// One secret per line, optionally followed by an RFC3339 expiry:
+//   new_secret
+//   old_secret 2026-11-01T00:00:00Z
+// Empty lines and lines starting with '#' are ignored. A line with an expiry
+// that does not parse is an error: dropping it would keep the secret valid forever.
+pub fn parse_secret_lines(raw: &str) -> Result<Vec<SecretEntry>, String> {
+    raw.lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'))
+        .map(|(i, l)| {
+            let mut parts = l.split_whitespace();
+            let value = parts.next().unwrap_or_default();
+            let expires_at = match (parts.next(), parts.next()) {
+                (None, _) => None,
+                (Some(e), None) => Some(
+                    DateTime::parse_from_rfc3339(e)
+                        .map_err(|err| format!("line {}: invalid expiry: {}", i + 1, err))?
+                        .with_timezone(&Utc),
+                ),
+                (Some(_), Some(_)) => return Err(format!("line {}: expected a secret and an optional expiry", i + 1)),
+            };
+            Ok(SecretEntry {
+                value: value.to_string(),
+                expires_at,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SecretStore {
+    entries: Vec<SecretEntry>,
+}
+
+impl SecretStore {
+    pub fn new(entries: Vec<SecretEntry>) -> Self {
+        SecretStore { entries }
+    }
+
+    // Sources, all combined:
+    //   LUZMO_PLUGIN_SECRET (+ LUZMO_PLUGIN_SECRET_PREVIOUS, LUZMO_PLUGIN_SECRET_PREVIOUS_EXPIRES),
+    //                             taken as is: spaces and a leading '#' are part of the secret
+    //   LUZMO_PLUGIN_SECRET_FILE  → lines as in parse_secret_lines
+    //   LUZMO_PLUGIN_SECRET_DIR   → mounted secret, every file holds one secret (+ expiry line)
+    // A configured source that cannot be read or parsed is an error. Only when
+    // no source is configured at all does development fall back to dev_secret.
+    pub fn load() -> Result<Self, String> {
+        let mut entries = vec![];
+        let mut configured = false;
+
+        if let Ok(s) = env::var("LUZMO_PLUGIN_SECRET") {
+            configured = true;
+            if !s.is_empty() {
+                entries.push(SecretEntry::new(&s));
+            }
+        }
+        if let Ok(s) = env::var("LUZMO_PLUGIN_SECRET_PREVIOUS") {
+            configured = true;
+            let expires_at = match env::var("LUZMO_PLUGIN_SECRET_PREVIOUS_EXPIRES") {
+                Ok(e) if !e.trim().is_empty() => Some(
+                    DateTime::parse_from_rfc3339(e.trim())
+                        .map_err(|err| format!("LUZMO_PLUGIN_SECRET_PREVIOUS_EXPIRES: {}", err))?
+                        .with_timezone(&Utc),
+                ),
+                _ => None,
+            };
+            if !s.is_empty() {
+                entries.push(SecretEntry { value: s, expires_at });
+            }
+        }
+        if let Ok(path) = env::var("LUZMO_PLUGIN_SECRET_FILE") {
+            configured = true;
+            let raw = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+            entries.extend(parse_secret_lines(&raw).map_err(|e| format!("{}: {}", path, e))?);
+        }
+        if let Ok(dir) = env::var("LUZMO_PLUGIN_SECRET_DIR") {
+            configured = true;
+            let mut paths: Vec<_> = std::fs::read_dir(&dir)
+                .map_err(|e| format!("{}: {}", dir, e))?
+                .flatten()
+                .map(|f| f.path())
+                // k8s mounts ..data symlinks next to the real files
+                .filter(|p| p.is_file())
+                .filter(|p| !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+                .collect();
+            paths.sort();
+            for p in paths {
+                let raw = std::fs::read_to_string(&p).map_err(|e| format!("{}: {}", p.display(), e))?;
+                let joined = raw.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+                entries.extend(parse_secret_lines(&joined).map_err(|e| format!("{}: {}", p.display(), e))?);
+            }
+        }
+
+        if !configured {
+            // In production: moet gezet zijn
+            // In development: fallback ok
+            let mode = env::var("NODE_ENV").unwrap_or_else(|_| "development".to_string());
+            if mode == "development" {
+                entries.push(SecretEntry::new("dev_secret"));
+            }
         }
+
+        Ok(SecretStore::new(entries))
+    }
+
+    pub fn is_configured(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    pub fn is_valid(&self, got: &str, now: DateTime<Utc>) -> bool {
+        // alle entries vergelijken, geen short-circuit
+        let matched = self.entries.iter().fold(false, |acc, e| {
+            acc | (constant_time_eq(e.value.as_bytes(), got.as_bytes()) & e.is_active(now))
+        });
+        !got.is_empty() && matched
+    }
+}
+
+fn reload_interval() -> Duration {
+    let secs = env::var("LUZMO_PLUGIN_SECRET_RELOAD_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+fn cache() -> &'static RwLock<SecretStore> {
+    static CACHE: OnceLock<RwLock<SecretStore>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        // main loads the secrets before serving, so this only fails outside the server
+        let store = SecretStore::load().unwrap_or_else(|e| {
+            log::error!(error = e.as_str(); "could not load secrets");
+            SecretStore::default()
+        });
+        RwLock::new(store)
     })
 }
 
+// Re-reads every source right away. Blocking file I/O: call it off the async
+// workers. On error the secrets loaded before stay in use.
+pub fn reload_secrets() -> Result<(), String> {
+    let fresh = SecretStore::load()?;
+    if let Ok(mut store) = cache().write() {
+        *store = fresh;
+    }
+    Ok(())
+}
+
+// Reloads every LUZMO_PLUGIN_SECRET_RELOAD_SECS on the blocking pool, so
+// rotating a mounted secret needs no restart. main spawns it once: one reload
+// at a time, and requests only ever read the cache.
+pub async fn reload_secrets_periodically() {
+    let mut tick = actix_web::rt::time::interval(reload_interval());
+    // the first tick fires right away, main has just loaded them
+    tick.tick().await;
+    loop {
+        tick.tick().await;
+        let result = web::block(reload_secrets).await.unwrap_or_else(|e| Err(e.to_string()));
+        if let Err(e) = result {
+            log::error!(error = e.as_str(); "could not reload secrets, keeping the previous ones");
+        }
+    }
+}
+
+pub fn secret_store() -> SecretStore {
+    cache().read().map(|c| c.clone()).unwrap_or_default()
+}
+
+#[derive(Debug, Clone)]
+struct FailureState {
+    count: u32,
+    first_at: Instant,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Failures {
+    by_client: HashMap<String, FailureState>,
+    pruned_at: Option<Instant>,
+}
+
+// Expired entries are dropped at most this often, on the next failure.
+const PRUNE_EVERY: Duration = Duration::from_secs(10);
+
+// Brute-force protection: `max_failures` failed attempts from one client within
+// `window` lock that client out for `lockout`. Time is passed in so tests can
+// drive it.
+pub struct AuthThrottle {
+    max_failures: u32,
+    window: Duration,
+    lockout: Duration,
+    failures: Mutex<Failures>,
+}
+
+impl AuthThrottle {
+    pub fn new(max_failures: u32, window: Duration, lockout: Duration) -> Self {
+        AuthThrottle {
+            max_failures,
+            window,
+            lockout,
+            failures: Mutex::new(Failures::default()),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let num = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default)
+        };
+        AuthThrottle::new(
+            num("LUZMO_PLUGIN_AUTH_MAX_FAILURES", 5) as u32,
+            Duration::from_secs(num("LUZMO_PLUGIN_AUTH_WINDOW_SECS", 300)),
+            Duration::from_secs(num("LUZMO_PLUGIN_AUTH_LOCKOUT_SECS", 900)),
+        )
+    }
+
+    // Past its window and not locked out (anymore): the entry means nothing.
+    fn is_expired(&self, st: &FailureState, now: Instant) -> bool {
+        match st.locked_until {
+            Some(until) => now >= until,
+            None => now.duration_since(st.first_at) > self.window,
+        }
+    }
+
+    // Remaining lockout for this client, if it is locked out.
+    pub fn locked_for(&self, client: &str, now: Instant) -> Option<Duration> {
+        let failures = self.failures.lock().ok()?;
+        let until = failures.by_client.get(client)?.locked_until?;
+        until.checked_duration_since(now).filter(|d| !d.is_zero())
+    }
+
+    // Returns the number of failures in the current window.
+    pub fn record_failure(&self, client: &str, now: Instant) -> u32 {
+        let Ok(mut failures) = self.failures.lock() else {
+            return 0;
+        };
+        if failures.pruned_at.is_none_or(|t| now.duration_since(t) >= PRUNE_EVERY) {
+            failures.by_client.retain(|_, st| !self.is_expired(st, now));
+            failures.pruned_at = Some(now);
+        }
+
+        let st = failures.by_client.entry(client.to_string()).or_insert(FailureState {
+            count: 0,
+            first_at: now,
+            locked_until: None,
+        });
+        if self.is_expired(st, now) {
+            *st = FailureState {
+                count: 0,
+                first_at: now,
+                locked_until: None,
+            };
+        }
+
+        st.count += 1;
+        if st.count >= self.max_failures {
+            st.locked_until = Some(now + self.lockout);
+        }
+        st.count
+    }
+
+    pub fn record_success(&self, client: &str) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.by_client.remove(client);
+        }
+    }
+
+    // Clients currently tracked.
+    pub fn len(&self) -> usize {
+        self.failures.lock().map(|f| f.by_client.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn throttle() -> &'static AuthThrottle {
+    static THROTTLE: OnceLock<AuthThrottle> = OnceLock::new();
+    THROTTLE.get_or_init(AuthThrottle::from_env)
+}
+
+// Peer address, or the forwarded client address when running behind a proxy
+// such as ngrok (LUZMO_PLUGIN_TRUST_PROXY=true). X-Forwarded-For is not trusted
+// otherwise, a client could pick a new "ip" for every attempt.
+pub fn client_ip(req: &HttpRequest) -> String {
+    let trust_proxy = env::var("LUZMO_PLUGIN_TRUST_PROXY").is_ok_and(|v| v == "true" || v == "1");
+    let ip = if trust_proxy {
+        req.connection_info().realip_remote_addr().map(str::to_string)
+    } else {
+        req.peer_addr().map(|a| a.ip().to_string())
+    };
+    ip.unwrap_or_else(|| "unknown".to_string())
+}
+
+fn audit_auth_failure(req: &HttpRequest, client: &str, reason: &str, failures: u32) {
+    log::warn!(
+        target: "audit",
+        request_id = request_id(req).as_str(),
+        ip = client,
+        path = req.path(),
+        reason = reason,
+        failures = failures;
+        "auth failure"
+    );
+}
+
+// Counts a failed attempt (secret, credentials, ...) against the client and audits it.
+pub fn record_auth_failure(req: &HttpRequest, reason: &str) {
+    let client = client_ip(req);
+    let failures = throttle().record_failure(&client, Instant::now());
+    metrics().record_auth_failure();
+    audit_auth_failure(req, &client, reason, failures);
+}
+
+fn locked_out_response(remaining: Duration) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .insert_header(("Retry-After", remaining.as_secs().max(1).to_string()))
+        .json(json!({
+            "type": { "code": 429, "description": "Too Many Requests" },
+            "message": "Too many failed authentication attempts"
+        }))
+}
+
+// Lockout check for callers that authenticate through another path (tenant secrets).
+pub fn check_lockout(req: &HttpRequest) -> Result<(), HttpResponse> {
+    let client = client_ip(req);
+    match throttle().locked_for(&client, Instant::now()) {
+        Some(remaining) => {
+            audit_auth_failure(req, &client, "locked out", 0);
+            Err(locked_out_response(remaining))
+        }
+        None => Ok(()),
+    }
+}
+
 pub fn check_secret(req: &HttpRequest) -> Result<(), HttpResponse> {
     let got = req
         .headers()
@@ -23,16 +388,19 @@ pub fn check_secret(req: &HttpRequest) -> Result<(), HttpResponse> {
         .and_then(|v| v.to_str().ok())
         .unwrap_or("");
 
-    let expected = expected_secret();
+    check_lockout(req)?;
+    let store = secret_store();
 
-    if expected == "NOT_SET" {
+    if !store.is_configured() {
+        // geen panic: liever duidelijke 401/500 flow
         return Err(HttpResponse::InternalServerError().json(json!({
             "type": { "code": 500, "description": "Internal Server Error" },
             "message": "LUZMO_PLUGIN_SECRET is not set"
         })));
     }
 
-    if got != expected {
+    if !store.is_valid(got, Utc::now()) {
+        record_auth_failure(req, if got.is_empty() { "missing X-Secret" } else { "invalid X-Secret" });
         return Err(HttpResponse::Unauthorized().json(json!({
             "type": { "code": 401, "description": "Unauthorized" },
             "message": "Missing or invalid X-Secret"
@@ -40,4 +408,10 @@ pub fn check_secret(req: &HttpRequest) -> Result<(), HttpResponse> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Resets the client's failure count. Only once every check passed: a valid
+// X-Secret alone must not reset it, or the X-Token could be brute-forced.
+pub fn record_auth_success(req: &HttpRequest) {
+    throttle().record_success(&client_ip(req));
+}
@@ -0,0 +1,239 @@
+use actix_web::{HttpRequest, HttpResponse};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+
+use crate::utils::secret::{check_lockout, record_auth_failure};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DEFAULT_WINDOW: i64 = 300;
+
+// Signed requests, as an alternative to the static X-Secret:
+//   X-Timestamp: unix seconds
+//   X-Signature: hex(HMAC-SHA256(key, "<timestamp>.<METHOD>.<path>[?<query>].<raw body>"))
+// A signature is only accepted within `window` seconds of its timestamp, and
+// only once (replays inside the window are rejected too). main builds it and
+// passes it to the handlers and the rate limiter as app data.
+pub struct SigningConfig {
+    pub key: Option<Vec<u8>>,
+    pub required: bool,
+    pub window: i64,
+}
+
+impl SigningConfig {
+    pub fn from_env() -> Self {
+        SigningConfig {
+            key: env::var("LUZMO_PLUGIN_SIGNING_KEY")
+                .ok()
+                .filter(|k| !k.is_empty())
+                .map(String::into_bytes),
+            required: env::var("LUZMO_PLUGIN_SIGNING_REQUIRED").is_ok_and(|v| v == "true" || v == "1"),
+            window: env::var("LUZMO_PLUGIN_SIGNING_WINDOW_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_WINDOW),
+        }
+    }
+
+    // SIGNING_REQUIRED zonder key zou ongetekende requests doorlaten
+    pub fn validate(&self) -> Result<(), String> {
+        if self.required && self.key.is_none() {
+            return Err("LUZMO_PLUGIN_SIGNING_REQUIRED needs LUZMO_PLUGIN_SIGNING_KEY".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Signing off: no key, not required.
+impl Default for SigningConfig {
+    fn default() -> Self {
+        SigningConfig {
+            key: None,
+            required: false,
+            window: DEFAULT_WINDOW,
+        }
+    }
+}
+
+// What the signature covers besides the timestamp: the method, path and raw
+// query string too, so a signed body can't be replayed against another endpoint
+// or with other parameters.
+pub struct SignedRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub query: &'a str,
+    pub body: &'a [u8],
+}
+
+fn mac_for(key: &[u8], timestamp: i64, signed: &SignedRequest) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(signed.method.to_ascii_uppercase().as_bytes());
+    mac.update(b".");
+    mac.update(signed.path.as_bytes());
+    if !signed.query.is_empty() {
+        mac.update(b"?");
+        mac.update(signed.query.as_bytes());
+    }
+    mac.update(b".");
+    mac.update(signed.body);
+    mac
+}
+
+pub fn sign(key: &[u8], timestamp: i64, signed: &SignedRequest) -> String {
+    mac_for(key, timestamp, signed)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Signatures seen within the replay window, with their timestamp.
+#[derive(Default)]
+pub struct ReplayGuard {
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl ReplayGuard {
+    // true the first time a signature is seen, false for a replay.
+    pub fn first_use(&self, signature: &str, timestamp: i64, now: i64, window: i64) -> bool {
+        let Ok(mut seen) = self.seen.lock() else {
+            return false;
+        };
+        seen.retain(|_, ts| (now - *ts).abs() <= window);
+        seen.insert(signature.to_ascii_lowercase(), timestamp).is_none()
+    }
+}
+
+// Timestamp and MAC only; Ok carries the parsed timestamp.
+fn verify_mac(
+    key: &[u8],
+    timestamp: &str,
+    signature: &str,
+    signed: &SignedRequest,
+    now: i64,
+    window: i64,
+) -> Result<i64, String> {
+    let ts: i64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| "Invalid X-Timestamp".to_string())?;
+    if (now - ts).abs() > window {
+        return Err("X-Timestamp outside the allowed window".to_string());
+    }
+
+    let expected = decode_hex(signature.trim()).ok_or_else(|| "Invalid X-Signature".to_string())?;
+    // verify_slice vergelijkt in constante tijd
+    mac_for(key, ts, signed)
+        .verify_slice(&expected)
+        .map_err(|_| "Invalid X-Signature".to_string())?;
+    Ok(ts)
+}
+
+// Checks timestamp, signature and replay; Err carries the reason.
+pub fn verify_signature(
+    key: &[u8],
+    timestamp: &str,
+    signature: &str,
+    signed: &SignedRequest,
+    now: i64,
+    window: i64,
+    replays: &ReplayGuard,
+) -> Result<(), String> {
+    let ts = verify_mac(key, timestamp, signature, signed, now, window)?;
+    if !replays.first_use(signature.trim(), ts, now, window) {
+        return Err("Replayed X-Signature".to_string());
+    }
+    Ok(())
+}
+
+fn replays() -> &'static ReplayGuard {
+    static REPLAYS: OnceLock<ReplayGuard> = OnceLock::new();
+    REPLAYS.get_or_init(ReplayGuard::default)
+}
+
+// The rate-limit client for a correctly signed request: the signing key
+// (hashed). The replay guard is left alone, check_signature still does that
+// when the handler runs.
+pub fn signed_client(req: &HttpRequest, body: &[u8], cfg: &SigningConfig) -> Option<String> {
+    let key = cfg.key.as_ref()?;
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let signed = SignedRequest {
+        method: req.method().as_str(),
+        path: req.path(),
+        query: req.query_string(),
+        body,
+    };
+    let now = chrono::Utc::now().timestamp();
+    verify_mac(
+        key,
+        header("X-Timestamp")?,
+        header("X-Signature")?,
+        &signed,
+        now,
+        cfg.window,
+    )
+    .ok()?;
+    let hash: String = Sha256::digest(key).iter().take(6).map(|b| format!("{:02x}", b)).collect();
+    Some(format!("signed:{}", hash))
+}
+
+fn unauthorized(message: &str) -> HttpResponse {
+    HttpResponse::Unauthorized().json(json!({
+        "type": { "code": 401, "description": "Unauthorized" },
+        "message": message
+    }))
+}
+
+// Ok(true) when the request carried a valid signature, Ok(false) when it is not
+// signed and signing is optional (the caller falls back to X-Secret).
+pub fn check_signature(req: &HttpRequest, body: &[u8], cfg: &SigningConfig) -> Result<bool, HttpResponse> {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+
+    let Some(key) = &cfg.key else {
+        if cfg.required {
+            // main weigert al te starten; dit vangt embedded gebruik op
+            log::error!("request signing is required but LUZMO_PLUGIN_SIGNING_KEY is not set");
+            return Err(unauthorized("Request signing is not configured"));
+        }
+        return Ok(false);
+    };
+    let Some(signature) = header("X-Signature") else {
+        if cfg.required {
+            record_auth_failure(req, "missing X-Signature");
+            return Err(unauthorized("Missing X-Signature"));
+        }
+        return Ok(false);
+    };
+
+    check_lockout(req)?;
+    let timestamp = header("X-Timestamp").unwrap_or("");
+    let now = chrono::Utc::now().timestamp();
+    let signed = SignedRequest {
+        method: req.method().as_str(),
+        path: req.path(),
+        query: req.query_string(),
+        body,
+    };
+    verify_signature(key, timestamp, signature, &signed, now, cfg.window, replays()).map_err(|reason| {
+        record_auth_failure(req, &reason);
+        unauthorized(&reason)
+    })?;
+    Ok(true)
+}
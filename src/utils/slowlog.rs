@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::engine::context::QueryContext;
+use crate::engine::execute::run;
+use crate::engine::stats::{elapsed_ms, QueryStats};
+use crate::errors::PluginError;
+use crate::luzmo::types::QueryRequest;
+use crate::utils::credentials::Identity;
+use crate::utils::policies::RowPolicies;
+use crate::utils::redact::RedactConfig;
+
+// One line of the slow query log. `body` is the request as sent by Luzmo, with
+// sensitive fields redacted (see utils::redact). Only entries where nothing was
+// redacted (`redacted: false`) can be replayed as-is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlowQueryEntry {
+    pub ts: String,
+    pub request_id: String,
+    pub dataset_id: String,
+    pub user: String,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    pub elapsed_ms: f64,
+    pub body: Value,
+    #[serde(default)]
+    pub redacted: bool,
+    #[serde(default)]
+    pub plan: Value,
+    #[serde(default)]
+    pub stats: QueryStats,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlowLogConfig {
+    pub path: PathBuf,
+    pub threshold_ms: f64,
+    pub max_bytes: u64,
+    // Rotated files kept next to the current one: path.1 (newest) .. path.N
+    pub keep: usize,
+}
+
+impl SlowLogConfig {
+    // Off unless LUZMO_PLUGIN_SLOW_QUERY_MS is set.
+    pub fn from_env() -> Option<Self> {
+        let threshold_ms = env::var("LUZMO_PLUGIN_SLOW_QUERY_MS").ok()?.parse().ok()?;
+        let num = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default)
+        };
+        Some(SlowLogConfig {
+            path: env::var("LUZMO_PLUGIN_SLOW_QUERY_LOG")
+                .unwrap_or_else(|_| "slow_queries.jsonl".to_string())
+                .into(),
+            threshold_ms,
+            max_bytes: num("LUZMO_PLUGIN_SLOW_QUERY_MAX_BYTES", 10 * 1024 * 1024),
+            keep: num("LUZMO_PLUGIN_SLOW_QUERY_KEEP", 3) as usize,
+        })
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(format!(".{}", n));
+    p.into()
+}
+
+pub struct SlowQueryLog {
+    config: SlowLogConfig,
+    redact: RedactConfig,
+    // one writer at a time, rotation included
+    lock: Mutex<()>,
+}
+
+impl SlowQueryLog {
+    pub fn new(config: SlowLogConfig) -> Self {
+        SlowQueryLog {
+            config,
+            redact: RedactConfig::from_env(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    // Redacted copy of a request body, and whether anything was redacted.
+    pub fn redact_body(&self, body: &Value) -> (Value, bool) {
+        let redacted = self.redact.value(body);
+        let changed = redacted != *body;
+        (redacted, changed)
+    }
+
+    pub fn config(&self) -> &SlowLogConfig {
+        &self.config
+    }
+
+    pub fn is_slow(&self, elapsed_ms: f64) -> bool {
+        elapsed_ms >= self.config.threshold_ms
+    }
+
+    // Blocking file I/O: call it off the async workers (web::block).
+    pub fn record(&self, entry: &SlowQueryEntry) -> Result<(), String> {
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+        let path = &self.config.path;
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.config.max_bytes {
+            self.rotate()?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| f.write_all(line.as_bytes()))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn rotate(&self) -> Result<(), String> {
+        let path = &self.config.path;
+        if self.config.keep == 0 {
+            return fs::remove_file(path).map_err(|e| e.to_string());
+        }
+        let _ = fs::remove_file(rotated(path, self.config.keep));
+        for n in (1..self.config.keep).rev() {
+            let from = rotated(path, n);
+            if from.exists() {
+                fs::rename(&from, rotated(path, n + 1)).map_err(|e| e.to_string())?;
+            }
+        }
+        fs::rename(path, rotated(path, 1)).map_err(|e| e.to_string())
+    }
+}
+
+pub fn slow_query_log() -> Option<&'static SlowQueryLog> {
+    static LOG: OnceLock<Option<SlowQueryLog>> = OnceLock::new();
+    LOG.get_or_init(|| SlowLogConfig::from_env().map(SlowQueryLog::new))
+        .as_ref()
+}
+
+pub fn read_entries(path: &Path) -> Result<Vec<SlowQueryEntry>, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    raw.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| serde_json::from_str(l).map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e)))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayResult {
+    pub rows: usize,
+    pub elapsed_ms: f64,
+    pub stats: QueryStats,
+}
+
+// Runs a recorded query again, as the recorded user (roles and tenant), with the
+// row-level security policies given. Nothing goes over HTTP.
+pub fn replay(entry: &SlowQueryEntry, policies: &RowPolicies) -> Result<ReplayResult, PluginError> {
+    // "[REDACTED]" in plaats van de echte waarde geeft een andere query
+    if entry.redacted {
+        return Err(PluginError::InvalidRequest {
+            message: "Body has redacted fields, it can't be replayed as-is".to_string(),
+        });
+    }
+    let req: QueryRequest = serde_json::from_value(entry.body.clone()).map_err(|e| PluginError::InvalidRequest {
+        message: format!("Json deserialize error: {}", e),
+    })?;
+    let identity = Identity {
+        user: entry.user.clone(),
+        tenant: entry.tenant.clone(),
+        roles: entry.roles.clone(),
+    };
+    let row_filters = policies
+        .filters_for(&identity, &entry.dataset_id)
+        .ok_or_else(|| PluginError::DatasetNotFound {
+            message: format!("No row policy for {} on {}", identity.user, entry.dataset_id),
+        })?;
+    let ctx = QueryContext {
+        row_filters,
+        identity,
+        request_id: entry.request_id.clone(),
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    let (rows, stats) = run(&req, &ctx)?;
+    Ok(ReplayResult {
+        rows: rows.len(),
+        elapsed_ms: elapsed_ms(started),
+        stats,
+    })
+}
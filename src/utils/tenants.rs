@@ -0,0 +1,80 @@
+use serde::Deserialize;
+use std::env;
+
+use crate::utils::credentials::Identity;
+use crate::utils::secret::constant_time_eq;
+
+// Tenant configuration, loaded from LUZMO_PLUGIN_TENANTS_FILE:
+//   [{ "id": "acme", "datasets": ["demo"], "secrets": ["acme_secret"] }]
+// A tenant is recognised by its own X-Secret or by the tenant of the caller's
+// X-Key/X-Token identity.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tenant {
+    pub id: String,
+    #[serde(default)]
+    pub datasets: Vec<String>,
+    #[serde(default)]
+    pub secrets: Vec<String>,
+}
+
+// Default is single-tenant. A configured tenant list, even an empty one, is
+// multi-tenant: callers outside every tenant see nothing.
+#[derive(Debug, Clone, Default)]
+pub struct Tenants {
+    tenants: Vec<Tenant>,
+    configured: bool,
+}
+
+impl Tenants {
+    pub fn new(tenants: Vec<Tenant>) -> Self {
+        Tenants {
+            tenants,
+            configured: true,
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let tenants: Vec<Tenant> = serde_json::from_str(&raw).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Tenants::new(tenants))
+    }
+
+    // Without a tenants file the plugin is single-tenant and every caller sees
+    // every dataset. A file that cannot be loaded is an error: the server must
+    // not start without the isolation it was configured with.
+    pub fn from_env() -> Result<Self, String> {
+        match env::var("LUZMO_PLUGIN_TENANTS_FILE") {
+            Ok(path) => Tenants::from_file(&path),
+            Err(_) => Ok(Tenants::default()),
+        }
+    }
+
+    pub fn is_multi_tenant(&self) -> bool {
+        self.configured
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Tenant> {
+        self.tenants.iter().find(|t| t.id == id)
+    }
+
+    pub fn by_secret(&self, secret: &str) -> Option<&Tenant> {
+        if secret.is_empty() {
+            return None;
+        }
+        self.tenants
+            .iter()
+            .find(|t| t.secrets.iter().any(|s| constant_time_eq(s.as_bytes(), secret.as_bytes())))
+    }
+
+    pub fn can_access(&self, identity: &Identity, dataset_id: &str) -> bool {
+        if !self.is_multi_tenant() {
+            return true;
+        }
+        identity
+            .tenant
+            .as_deref()
+            .and_then(|t| self.get(t))
+            .map(|t| t.datasets.iter().any(|d| d == dataset_id))
+            .unwrap_or(false)
+    }
+}
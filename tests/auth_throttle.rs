@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{CredentialEntry, CredentialVerifier, StaticCredentials};
+use luzmo_plugin::utils::secret::{constant_time_eq, AuthThrottle};
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
+
+#[test]
+fn locks_out_after_max_failures_until_lockout_expires() {
+    let throttle = AuthThrottle::new(3, Duration::from_secs(60), Duration::from_secs(300));
+    let t0 = Instant::now();
+
+    assert_eq!(throttle.record_failure("10.0.0.1", t0), 1);
+    assert_eq!(throttle.record_failure("10.0.0.1", t0 + Duration::from_secs(1)), 2);
+    assert!(throttle.locked_for("10.0.0.1", t0 + Duration::from_secs(2)).is_none());
+
+    assert_eq!(throttle.record_failure("10.0.0.1", t0 + Duration::from_secs(2)), 3);
+    let remaining = throttle.locked_for("10.0.0.1", t0 + Duration::from_secs(3)).unwrap();
+    assert_eq!(remaining, Duration::from_secs(299));
+
+    // andere client blijft ongemoeid
+    assert!(throttle.locked_for("10.0.0.2", t0 + Duration::from_secs(3)).is_none());
+
+    assert!(throttle.locked_for("10.0.0.1", t0 + Duration::from_secs(302)).is_none());
+    assert_eq!(throttle.record_failure("10.0.0.1", t0 + Duration::from_secs(303)), 1);
+}
+
+#[test]
+fn success_and_window_reset_the_counter() {
+    let throttle = AuthThrottle::new(3, Duration::from_secs(60), Duration::from_secs(300));
+    let t0 = Instant::now();
+
+    throttle.record_failure("ip", t0);
+    throttle.record_failure("ip", t0);
+    throttle.record_success("ip");
+    assert_eq!(throttle.record_failure("ip", t0), 1);
+
+    throttle.record_failure("ip", t0);
+    assert_eq!(throttle.record_failure("ip", t0 + Duration::from_secs(61)), 1);
+    assert!(throttle.locked_for("ip", t0 + Duration::from_secs(61)).is_none());
+}
+
+#[test]
+fn expired_clients_are_evicted() {
+    let throttle = AuthThrottle::new(3, Duration::from_secs(60), Duration::from_secs(300));
+    let t0 = Instant::now();
+
+    for i in 0..100 {
+        throttle.record_failure(&format!("10.0.1.{i}"), t0);
+    }
+    for _ in 0..3 {
+        throttle.record_failure("locked", t0);
+    }
+    assert_eq!(throttle.len(), 101);
+
+    // window voorbij, lockout nog niet
+    throttle.record_failure("new", t0 + Duration::from_secs(61));
+    assert_eq!(throttle.len(), 2);
+
+    throttle.record_failure("later", t0 + Duration::from_secs(301));
+    assert_eq!(throttle.len(), 1);
+}
+
+#[actix_web::test]
+async fn valid_secret_does_not_reset_bad_token_failures() {
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(StaticCredentials::new(vec![CredentialEntry {
+        key: "key-1".into(),
+        token: "token-1".into(),
+        user: Some("alice".into()),
+        tenant: None,
+        roles: vec![],
+        host: None,
+    }]));
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(Tenants::default()))
+            .app_data(web::Data::new(SigningConfig::default()))
+            .route("/authorize", web::post().to(server::authorize::authorize)),
+    )
+    .await;
+
+    let attempt = |token: &str| {
+        actix_test::TestRequest::post()
+            .uri("/authorize")
+            .peer_addr("10.9.8.7:1234".parse().unwrap())
+            .insert_header(("X-Secret", "dev_secret"))
+            .insert_header(("X-Key", "key-1"))
+            .insert_header(("X-Token", token.to_string()))
+            .to_request()
+    };
+
+    // default: 5 failures
+    for _ in 0..5 {
+        assert_eq!(actix_test::call_service(&app, attempt("guess")).await.status(), 401);
+    }
+    let locked = actix_test::call_service(&app, attempt("token-1")).await;
+    assert_eq!(locked.status(), 429);
+}
+
+#[test]
+fn constant_time_eq_compares_full_values() {
+    assert!(constant_time_eq(b"dev_secret", b"dev_secret"));
+    assert!(!constant_time_eq(b"dev_secret", b"dev_secreT"));
+    assert!(!constant_time_eq(b"dev_secret", b"dev_secret2"));
+    assert!(!constant_time_eq(b"", b"x"));
+    assert!(constant_time_eq(b"", b""));
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use luzmo_plugin::engine::aggregation::execute_aggregation;
+use luzmo_plugin::engine::bins::MAX_BINS;
+use luzmo_plugin::engine::dataset::Schema;
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::engine::plan::build_plan;
+use luzmo_plugin::errors::PluginError;
+use luzmo_plugin::luzmo::types::{BinSpec, Column, QueryRequest};
+use serde_json::{json, Value};
+
+fn histogram(bins: BinSpec) -> QueryRequest {
+    QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![
+            Column { id: "value".into(), bins: Some(bins), ..Default::default() },
+            Column { id: "*".into(), aggregation: Some("count".into()), ..Default::default() },
+        ]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn fixed_width_bins_are_labeled_and_ordered() {
+    let rows = run(&histogram(BinSpec { width: Some(10.0), ..Default::default() }), &QueryContext::default()).unwrap().0;
+
+    let labels: Vec<&str> = rows.iter().map(|r| r[0].as_str().unwrap()).collect();
+    assert_eq!(labels.first(), Some(&"0-10"));
+    assert!(labels.windows(2).all(|w| w[0] != w[1]));
+    assert!(labels.contains(&"10-20"));
+    let idx_2 = labels.iter().position(|l| *l == "10-20").unwrap();
+    let idx_1 = labels.iter().position(|l| *l == "0-10").unwrap();
+    assert!(idx_1 < idx_2);
+}
+
+#[test]
+fn custom_edges_emit_empty_bins() {
+    let spec = BinSpec {
+        edges: Some(vec![0.0, 50.0, 1000.0, 2000.0]),
+        include_empty: true,
+        ..Default::default()
+    };
+    let rows = run(&histogram(spec), &QueryContext::default()).unwrap().0;
+
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[2], vec![json!("1000-2000"), json!(0)]);
+}
+
+#[test]
+fn bins_without_width_or_edges_are_rejected() {
+    assert!(run(&histogram(BinSpec::default()), &QueryContext::default()).is_err());
+}
+
+#[test]
+fn bin_counts_above_the_cap_are_rejected() {
+    // values span roughly 0-70, so this needs ~7 million empty bins
+    let tiny = BinSpec { width: Some(0.00001), include_empty: true, ..Default::default() };
+    let err = run(&histogram(tiny), &QueryContext::default()).unwrap_err();
+    assert!(matches!(err, PluginError::InvalidRequest { .. }), "{:?}", err);
+
+    let edges = BinSpec { edges: Some((0..=MAX_BINS).map(|e| e as f64).collect()), ..Default::default() };
+    assert!(run(&histogram(edges), &QueryContext::default()).is_err());
+}
+
+fn value_schema() -> Schema {
+    Schema {
+        col_index: HashMap::from([("value".to_string(), 0)]),
+        ..Default::default()
+    }
+}
+
+fn binned(specs: Vec<BinSpec>) -> QueryRequest {
+    let mut columns: Vec<Column> =
+        specs.into_iter().map(|b| Column { id: "value".into(), bins: Some(b), ..Default::default() }).collect();
+    columns.push(Column { id: "*".into(), aggregation: Some("count".into()), ..Default::default() });
+    QueryRequest { dataset_id: Some("demo".to_string()), columns: Some(columns), ..Default::default() }
+}
+
+#[test]
+fn custom_edges_are_half_open_with_one_overflow_bin() {
+    let data: Vec<Vec<Value>> = [-1.0, 0.0, 49.5, 50.0, 1999.5, 2000.0, 2500.0].iter().map(|v| vec![json!(v)]).collect();
+    let spec = BinSpec { edges: Some(vec![0.0, 50.0, 1000.0, 2000.0]), ..Default::default() };
+    let plan = build_plan(&binned(vec![spec]), &value_schema()).unwrap();
+    let rows = execute_aggregation(&data, &plan, &value_schema().col_index).unwrap();
+
+    assert_eq!(
+        rows,
+        vec![
+            vec![json!("<0"), json!(1)],
+            vec![json!("0-50"), json!(2)],
+            vec![json!("50-1000"), json!(1)],
+            vec![json!("1000-2000"), json!(1)],
+            vec![json!(">=2000"), json!(2)],
+        ]
+    );
+}
+
+#[test]
+fn empty_bins_are_only_added_next_to_groups_in_the_data() {
+    let data = vec![vec![json!(5)], vec![json!(25)]];
+    let edges = BinSpec { edges: Some(vec![0.0, 10.0, 20.0, 30.0]), include_empty: true, ..Default::default() };
+    let width = BinSpec { width: Some(10.0), include_empty: true, ..Default::default() };
+    let plan = build_plan(&binned(vec![edges, width]), &value_schema()).unwrap();
+    let rows = execute_aggregation(&data, &plan, &value_schema().col_index).unwrap();
+
+    // every edge bin next to the two observed width bins and the other way
+    // round, not every bin of one next to every filled bin of the other
+    assert_eq!(rows.len(), 8);
+    assert!(!rows.contains(&vec![json!("10-20"), json!("10-20"), json!(0)]));
+    assert_eq!(rows.iter().filter(|r| r[2] == json!(1)).count(), 2);
+}
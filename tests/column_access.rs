@@ -0,0 +1,96 @@
+use luzmo_plugin::engine::access::{
+    check_hash_salt, schema_for, strip_hidden_columns, ColumnAccess, Restriction,
+};
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::dataset::{demo_schema, Schema};
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::engine::plan::build_plan;
+use luzmo_plugin::luzmo::types::{Column, FilterExpr, QueryRequest};
+use luzmo_plugin::utils::credentials::Identity;
+use serde_json::json;
+
+fn with_role(role: Option<&str>) -> QueryContext {
+    QueryContext::new(Identity {
+        user: "u".into(),
+        roles: role.map(|r| vec![r.to_string()]).unwrap_or_default(),
+        ..Default::default()
+    })
+}
+
+fn customers() -> QueryRequest {
+    QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![Column { id: "customer".into(), ..Default::default() }]),
+        limit: Some(5),
+        ..Default::default()
+    }
+}
+
+fn restricted(restriction: Restriction) -> Schema {
+    let mut schema = demo_schema();
+    schema.access.insert(
+        "customer".into(),
+        ColumnAccess { restriction, roles: vec!["admin".into()] },
+    );
+    schema
+}
+
+#[test]
+fn customer_ids_are_hashed_unless_admin() {
+    let rows = run(&customers(), &with_role(None)).unwrap().0;
+    assert!(rows[0][0].as_str().unwrap().starts_with("h:"));
+
+    let rows = run(&customers(), &with_role(Some("admin"))).unwrap().0;
+    assert!(rows[0][0].as_str().unwrap().starts_with("CUST-"));
+}
+
+#[test]
+fn hidden_columns_are_unknown_and_unlisted() {
+    let schema = restricted(Restriction::Hidden);
+    let anon = Identity::anonymous();
+
+    assert!(build_plan(&customers(), &schema_for(&schema, &anon)).is_err());
+    assert!(build_plan(&customers(), &schema).is_ok());
+
+    let listed = strip_hidden_columns(
+        json!({ "id": "demo", "columns": [{ "id": "category" }, { "id": "customer" }] }),
+        &schema,
+        &anon,
+    );
+    assert_eq!(listed["columns"], json!([{ "id": "category" }]));
+}
+
+#[test]
+fn filters_only_see_the_hashed_values() {
+    let mut req = customers();
+    req.filters = Some(vec![FilterExpr {
+        column_id: Some("customer".into()),
+        expression: Some("=".into()),
+        id: None,
+        value: Some(json!(["CUST-0001"])),
+    }]);
+    assert!(run(&req, &with_role(None)).unwrap().0.is_empty());
+    let rows = run(&req, &with_role(Some("admin"))).unwrap().0;
+    assert!(!rows.is_empty());
+    assert!(rows.iter().all(|r| r[0] == json!("CUST-0001")));
+
+    // the hash itself does match, and is all that comes back
+    let hashed = run(&customers(), &with_role(None)).unwrap().0[0][0].clone();
+    req.filters.as_mut().unwrap()[0].value = Some(json!([hashed.clone()]));
+    let rows = run(&req, &with_role(None)).unwrap().0;
+    assert!(!rows.is_empty());
+    assert!(rows.iter().all(|r| r[0] == hashed));
+}
+
+#[test]
+fn hashed_columns_need_a_salt() {
+    assert!(check_hash_salt(&demo_schema(), None).is_err());
+    assert!(check_hash_salt(&demo_schema(), Some("pepper")).is_ok());
+    assert!(check_hash_salt(&restricted(Restriction::Masked), None).is_ok());
+
+    // without a configured salt the hash is not the plain unsalted digest
+    let plain = run(&customers(), &with_role(Some("admin"))).unwrap().0;
+    assert_eq!(plain[0][0], json!("CUST-0000"));
+    let hashed = run(&customers(), &with_role(None)).unwrap().0;
+    assert_ne!(hashed[0][0], json!("h:dbdcee95df9e0a9e"));
+}
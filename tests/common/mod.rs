@@ -0,0 +1,44 @@
+// Shared by the test binaries; not every binary uses every helper.
+#![allow(dead_code)]
+
+use std::env;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+static ENV: RwLock<()> = RwLock::new(());
+
+// Sets environment variables for the length of a test and puts the previous
+// values back when dropped. Holds the env lock meanwhile, so tests that read
+// the same variables (see read_env) don't run at the same time.
+pub struct EnvGuard {
+    saved: Vec<(String, Option<String>)>,
+    _lock: RwLockWriteGuard<'static, ()>,
+}
+
+pub fn set_env(vars: &[(&str, &str)]) -> EnvGuard {
+    let lock = ENV.write().unwrap_or_else(PoisonError::into_inner);
+    let saved = vars
+        .iter()
+        .map(|(name, value)| {
+            let old = env::var(name).ok();
+            env::set_var(name, value);
+            (name.to_string(), old)
+        })
+        .collect();
+    EnvGuard { saved, _lock: lock }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (name, old) in self.saved.drain(..).rev() {
+            match old {
+                Some(value) => env::set_var(&name, value),
+                None => env::remove_var(&name),
+            }
+        }
+    }
+}
+
+// For tests whose outcome depends on the environment: keeps set_env out while held.
+pub fn read_env() -> RwLockReadGuard<'static, ()> {
+    ENV.read().unwrap_or_else(PoisonError::into_inner)
+}
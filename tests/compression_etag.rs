@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use actix_web::middleware::Compress;
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{AllowAll, CredentialVerifier};
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
+use serde_json::json;
+
+macro_rules! app {
+    () => {{
+        let verifier: Arc<dyn CredentialVerifier> = Arc::new(AllowAll);
+        actix_test::init_service(
+            App::new()
+                .wrap(Compress::default())
+                .app_data(web::Data::from(verifier))
+                .app_data(web::Data::new(Tenants::default()))
+                .app_data(web::Data::new(RowPolicies::default()))
+                .app_data(web::Data::new(SigningConfig::default()))
+                .route("/query", web::post().to(server::query::handle_query)),
+        )
+        .await
+    }};
+}
+
+fn raw_query() -> actix_test::TestRequest {
+    actix_test::TestRequest::post()
+        .uri("/query")
+        .insert_header(("X-Secret", "dev_secret"))
+        .set_json(json!({"dataset_id": "demo", "columns": [{"id": "category"}, {"id": "value"}]}))
+}
+
+#[actix_web::test]
+async fn responses_are_compressed_when_accepted() {
+    let app = app!();
+
+    let gzip = actix_test::call_service(&app, raw_query().insert_header(("Accept-Encoding", "gzip")).to_request()).await;
+    assert_eq!(gzip.headers().get("Content-Encoding").unwrap(), "gzip");
+
+    let br = actix_test::call_service(&app, raw_query().insert_header(("Accept-Encoding", "br")).to_request()).await;
+    assert_eq!(br.headers().get("Content-Encoding").unwrap(), "br");
+
+    let plain = actix_test::call_service(&app, raw_query().to_request()).await;
+    assert!(plain.headers().get("Content-Encoding").is_none());
+}
+
+#[actix_web::test]
+async fn matching_etag_gets_304() {
+    let app = app!();
+
+    let first = actix_test::call_service(&app, raw_query().to_request()).await;
+    assert_eq!(first.status(), 200);
+    let etag = first.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/\""));
+
+    let again = actix_test::call_service(&app, raw_query().insert_header(("If-None-Match", etag.as_str())).to_request()).await;
+    assert_eq!(again.status(), 304);
+    assert_eq!(again.headers().get("ETag").unwrap(), etag.as_str());
+    assert!(actix_test::read_body(again).await.is_empty());
+
+    // weak comparison: the same tag without W/ matches too
+    let strong = actix_test::call_service(&app, raw_query().insert_header(("If-None-Match", &etag[2..])).to_request()).await;
+    assert_eq!(strong.status(), 304);
+
+    let stale = actix_test::call_service(&app, raw_query().insert_header(("If-None-Match", "\"1-stale\"")).to_request()).await;
+    assert_eq!(stale.status(), 200);
+
+    // "*" is ignored
+    let any = actix_test::call_service(&app, raw_query().insert_header(("If-None-Match", "*")).to_request()).await;
+    assert_eq!(any.status(), 200);
+}
+
+#[actix_web::test]
+async fn etag_depends_on_the_query() {
+    let app = app!();
+
+    let a = actix_test::call_service(&app, raw_query().to_request()).await;
+    let b = actix_test::call_service(
+        &app,
+        actix_test::TestRequest::post()
+            .uri("/query")
+            .insert_header(("X-Secret", "dev_secret"))
+            .set_json(json!({"dataset_id": "demo", "columns": [{"id": "category"}]}))
+            .to_request(),
+    )
+    .await;
+    assert_ne!(a.headers().get("ETag"), b.headers().get("ETag"));
+}
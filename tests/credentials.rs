@@ -0,0 +1,88 @@
+mod common;
+
+use std::sync::Arc;
+
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{
+    verifier_from_env, AllowAll, CredentialEntry, CredentialVerifier, Credentials, StaticCredentials,
+};
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
+
+fn static_verifier() -> StaticCredentials {
+    StaticCredentials::new(vec![CredentialEntry {
+        key: "key-1".into(),
+        token: "token-1".into(),
+        user: Some("alice".into()),
+        tenant: Some("acme".into()),
+        roles: vec!["analyst".into()],
+        host: None,
+    }])
+}
+
+fn creds(key: &str, token: &str) -> Credentials {
+    Credentials {
+        key: Some(key.into()),
+        token: Some(token.into()),
+        host: None,
+    }
+}
+
+#[test]
+fn static_credentials_map_to_identity() {
+    let id = static_verifier().verify(&creds("key-1", "token-1")).unwrap();
+    assert_eq!(id.user, "alice");
+    assert_eq!(id.tenant.as_deref(), Some("acme"));
+    assert_eq!(id.roles, vec!["analyst".to_string()]);
+
+    assert!(static_verifier().verify(&creds("key-1", "wrong")).is_err());
+    assert!(static_verifier().verify(&Credentials::default()).is_err());
+}
+
+#[test]
+fn allow_all_uses_key_as_user() {
+    assert_eq!(AllowAll.verify(&creds("k", "t")).unwrap().user, "k");
+    assert_eq!(AllowAll.verify(&Credentials::default()).unwrap().user, "anonymous");
+}
+
+#[actix_web::test]
+async fn authorize_rejects_invalid_credentials() {
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(static_verifier());
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(Tenants::default()))
+            .app_data(web::Data::new(SigningConfig::default()))
+            .route("/authorize", web::post().to(server::authorize::authorize)),
+    )
+    .await;
+
+    let ok = actix_test::TestRequest::post()
+        .uri("/authorize")
+        .insert_header(("X-Secret", "dev_secret"))
+        .insert_header(("X-Key", "key-1"))
+        .insert_header(("X-Token", "token-1"))
+        .to_request();
+    assert_eq!(actix_test::call_service(&app, ok).await.status(), 200);
+
+    let bad = actix_test::TestRequest::post()
+        .uri("/authorize")
+        .insert_header(("X-Secret", "dev_secret"))
+        .insert_header(("X-Key", "key-1"))
+        .insert_header(("X-Token", "nope"))
+        .to_request();
+    assert_eq!(actix_test::call_service(&app, bad).await.status(), 401);
+}
+
+#[test]
+fn unreadable_or_invalid_credentials_file_is_an_error() {
+    let broken = std::env::temp_dir().join(format!("credentials-{}.json", std::process::id()));
+    std::fs::write(&broken, "[{ \"key\": ").unwrap();
+
+    for path in ["/nonexistent/luzmo-credentials.json", broken.to_str().unwrap()] {
+        let _env = common::set_env(&[("LUZMO_PLUGIN_CREDENTIALS_FILE", path)]);
+        assert!(verifier_from_env().is_err(), "{}", path);
+    }
+    std::fs::remove_file(&broken).ok();
+}
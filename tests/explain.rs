@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::explain::explain;
+use luzmo_plugin::luzmo::types::QueryRequest;
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{AllowAll, CredentialVerifier, Identity};
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
+use serde_json::{json, Value};
+
+fn request(body: Value) -> QueryRequest {
+    serde_json::from_value(body).unwrap()
+}
+
+#[test]
+fn explain_resolves_group_cols_measures_and_filters() {
+    let plan = explain(
+        &request(json!({
+            "dataset_id": "demo",
+            "columns": [
+                {"id": "category", "level": "division"},
+                {"id": "value", "aggregation": "sum", "window": "running_sum"}
+            ],
+            "filters": [{"column_id": "category", "expression": "equal", "value": ["Consumer"]}],
+            "options": {"sort": [{"column_index": 1, "direction": "desc"}]},
+            "limit": 5
+        })),
+        &QueryContext::new(Identity::anonymous()),
+    )
+    .unwrap();
+
+    assert_eq!(plan["backend"], "memory");
+    assert_eq!(plan["pushdown"], false);
+    assert!(plan["sql"].is_null());
+    assert_eq!(plan["mode"], "aggregate");
+    assert_eq!(plan["group_cols"][0]["drill"], json!({"depth": 1, "level": "division"}));
+    assert_eq!(plan["measures"][0]["aggregation"], "sum");
+    assert_eq!(plan["measures"][0]["window"], "running_sum");
+    assert_eq!(plan["filters"][0]["operator"], "==");
+    assert_eq!(plan["sort"][0]["direction"], "desc");
+    assert_eq!(plan["limit"], 5);
+}
+
+#[test]
+fn explain_reports_the_same_validation_errors_as_execution() {
+    let err = explain(
+        &request(json!({
+            "dataset_id": "demo",
+            "columns": [{"id": "category"}, {"id": "value", "aggregation": "median"}]
+        })),
+        &QueryContext::new(Identity::anonymous()),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), "UnsupportedAggregation");
+}
+
+#[actix_web::test]
+async fn explain_endpoint_returns_the_plan() {
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(AllowAll);
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(Tenants::default()))
+            .app_data(web::Data::new(RowPolicies::default()))
+            .app_data(web::Data::new(SigningConfig::default()))
+            .route("/query/explain", web::post().to(server::query::handle_explain)),
+    )
+    .await;
+
+    let res = actix_test::call_service(
+        &app,
+        actix_test::TestRequest::post()
+            .uri("/query/explain")
+            .insert_header(("X-Secret", "dev_secret"))
+            .set_json(json!({"dataset_id": "demo", "columns": [{"id": "category"}]}))
+            .to_request(),
+    )
+    .await;
+    assert!(res.status().is_success());
+    let plan: Value = actix_test::read_body_json(res).await;
+    assert_eq!(plan["mode"], "raw");
+    assert_eq!(plan["dataset_id"], "demo");
+}
@@ -0,0 +1,54 @@
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::luzmo::types::{Column, FilterExpr, QueryRequest};
+use serde_json::json;
+
+fn by_category(level: Option<&str>, filters: Option<Vec<FilterExpr>>) -> QueryRequest {
+    QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![
+            Column {
+                id: "category".into(),
+                r#type: Some("hierarchy".into()),
+                level: level.map(Into::into),
+                ..Default::default()
+            },
+            Column { id: "value".into(), aggregation: Some("sum".into()), ..Default::default() },
+        ]),
+        filters,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn groups_at_requested_level() {
+    let top = run(&by_category(Some("1"), None), &QueryContext::default()).unwrap().0;
+    let names: Vec<_> = top.iter().map(|r| r[0].clone()).collect();
+    assert_eq!(names, vec![json!("Business"), json!("Consumer")]);
+
+    let segments = run(&by_category(Some("segment"), None), &QueryContext::default()).unwrap().0;
+    assert_eq!(segments.len(), 4);
+
+    let leaves = run(&by_category(None, None), &QueryContext::default()).unwrap().0;
+    assert_eq!(leaves.len(), 6);
+    assert_eq!(leaves[0][0], json!("A"));
+}
+
+#[test]
+fn filter_matches_any_level() {
+    let consumer = vec![FilterExpr {
+        column_id: Some("category".into()),
+        id: None,
+        expression: Some("=".into()),
+        value: Some(json!("Consumer")),
+    }];
+    let rows = run(&by_category(None, Some(consumer)), &QueryContext::default()).unwrap().0;
+    let names: Vec<_> = rows.iter().map(|r| r[0].clone()).collect();
+    assert_eq!(names, vec![json!("A"), json!("B"), json!("C")]);
+}
+
+#[test]
+fn unknown_level_is_rejected() {
+    assert!(run(&by_category(Some("4"), None), &QueryContext::default()).is_err());
+    assert!(run(&by_category(Some("region"), None), &QueryContext::default()).is_err());
+}
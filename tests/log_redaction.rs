@@ -0,0 +1,48 @@
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use luzmo_plugin::utils::redact::{truncate, RedactConfig, REDACTED};
+
+#[test]
+fn auth_headers_are_redacted() {
+    let mut headers = HeaderMap::new();
+    for (k, v) in [
+        ("x-secret", "dev_secret"),
+        ("x-token", "token-1"),
+        ("x-key", "key-1"),
+        ("content-type", "application/json"),
+    ] {
+        headers.insert(HeaderName::from_static(k), HeaderValue::from_static(v));
+    }
+
+    let out = RedactConfig::default().headers(&headers);
+    assert!(!out.contains("dev_secret"));
+    assert!(!out.contains("token-1"));
+    assert!(out.contains(&format!("x-secret: {}", REDACTED)));
+    assert!(out.contains("x-key: key-1"));
+    assert!(out.contains("content-type: application/json"));
+}
+
+#[test]
+fn configured_body_fields_are_redacted_at_any_depth() {
+    let cfg = RedactConfig {
+        fields: vec!["value".into(), "password".into()],
+        max_body: 4096,
+    };
+    let body = br#"{"id":"demo","filters":[{"column_id":"customer","expression":"=","value":["CUST-0001"]}],"password":"hunter2"}"#;
+
+    let out = cfg.body(body);
+    assert!(!out.contains("CUST-0001"));
+    assert!(!out.contains("hunter2"));
+    assert!(out.contains("\"column_id\":\"customer\""));
+}
+
+#[test]
+fn large_bodies_are_truncated() {
+    let cfg = RedactConfig {
+        max_body: 10,
+        ..Default::default()
+    };
+    let out = cfg.body("x".repeat(100).as_bytes());
+    assert_eq!(out, format!("{}... (90 bytes truncated)", "x".repeat(10)));
+    // never splits a multi-byte character
+    assert_eq!(truncate("ééé", 3), "é... (4 bytes truncated)");
+}
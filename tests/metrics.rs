@@ -0,0 +1,74 @@
+use actix_web::middleware::from_fn;
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::errors::PluginError;
+use luzmo_plugin::luzmo::types::{Column, QueryRequest};
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::Identity;
+use luzmo_plugin::utils::metrics::Metrics;
+
+fn sum_by_category() -> QueryRequest {
+    QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![
+            Column { id: "category".into(), ..Default::default() },
+            Column { id: "value".into(), aggregation: Some("sum".into()), ..Default::default() },
+        ]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn query_stats_count_scanned_and_returned_rows() {
+    let ctx = QueryContext::new(Identity::anonymous());
+    let (rows, stats) = run(&sum_by_category(), &ctx).unwrap();
+
+    assert!(stats.rows_scanned > rows.len());
+    assert_eq!(stats.rows_filtered, stats.rows_scanned);
+    assert_eq!(stats.groups, rows.len());
+    assert_eq!(stats.rows_returned, rows.len());
+}
+
+#[test]
+fn registry_renders_prometheus_text() {
+    let m = Metrics::default();
+    m.record_request("/query", 200, 0.02);
+    m.record_request("/query", 200, 0.3);
+    m.record_error("UnknownColumn");
+    m.record_auth_failure();
+
+    let out = m.render();
+    assert!(out.contains("# TYPE luzmo_plugin_http_request_duration_seconds histogram"));
+    assert!(out.contains("luzmo_plugin_http_requests_total{route=\"/query\",status=\"200\"} 2"));
+    assert!(out.contains("luzmo_plugin_http_request_duration_seconds_bucket{route=\"/query\",le=\"0.025\"} 1"));
+    assert!(out.contains("luzmo_plugin_http_request_duration_seconds_bucket{route=\"/query\",le=\"+Inf\"} 2"));
+    assert!(out.contains("luzmo_plugin_http_request_duration_seconds_count{route=\"/query\"} 2"));
+    assert!(out.contains("luzmo_plugin_errors_total{variant=\"UnknownColumn\"} 1"));
+    assert!(out.contains("luzmo_plugin_auth_failures_total 1"));
+}
+
+async fn unknown_column() -> Result<String, PluginError> {
+    Err(PluginError::UnknownColumn {
+        message: "nope".into(),
+    })
+}
+
+#[actix_web::test]
+async fn middleware_tracks_routes_and_error_variants() {
+    let app = actix_test::init_service(
+        App::new()
+            .wrap(from_fn(server::middleware::track_metrics))
+            .route("/fails/{id}", web::get().to(unknown_column))
+            .route("/metrics", web::get().to(server::metrics::handle_metrics)),
+    )
+    .await;
+
+    actix_test::call_service(&app, actix_test::TestRequest::get().uri("/fails/1").to_request()).await;
+    let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/metrics").to_request()).await;
+    let body = String::from_utf8(actix_test::read_body(res).await.to_vec()).unwrap();
+
+    // route pattern, not the concrete path
+    assert!(body.contains("luzmo_plugin_http_requests_total{route=\"/fails/{id}\",status=\"400\"} 1"));
+    assert!(body.contains("luzmo_plugin_errors_total{variant=\"UnknownColumn\"} 1"));
+}
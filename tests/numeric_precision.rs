@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use luzmo_plugin::engine::aggregation::execute_aggregation;
+use luzmo_plugin::engine::dataset::Schema;
+use luzmo_plugin::engine::numeric::NumberFormat;
+use luzmo_plugin::engine::plan::build_plan;
+use luzmo_plugin::luzmo::types::{Column, QueryRequest};
+use serde_json::json;
+
+fn schema(format: NumberFormat) -> Schema {
+    Schema {
+        col_index: HashMap::from([("label".to_string(), 0), ("amount".to_string(), 1)]),
+        formats: HashMap::from([("amount".to_string(), format)]),
+        ..Default::default()
+    }
+}
+
+fn sum_and_avg() -> QueryRequest {
+    QueryRequest {
+        columns: Some(vec![
+            Column { id: "label".into(), ..Default::default() },
+            Column { id: "amount".into(), aggregation: Some("sum".into()), ..Default::default() },
+            Column { id: "amount".into(), aggregation: Some("avg".into()), ..Default::default() },
+        ]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn decimal_columns_sum_without_drift() {
+    let schema = schema(NumberFormat { precision: 10, decimal: true, integer: false });
+    let rows: Vec<_> = (0..10).map(|_| vec![json!("x"), json!(0.1)]).collect();
+
+    let plan = build_plan(&sum_and_avg(), &schema).unwrap();
+    let out = execute_aggregation(&rows, &plan, &schema.col_index).unwrap();
+
+    assert_eq!(out[0][1], json!(1.0));
+    assert_eq!(out[0][2], json!(0.1));
+}
+
+#[test]
+fn integer_columns_stay_integers() {
+    let schema = schema(NumberFormat { precision: 0, decimal: false, integer: true });
+    let rows = vec![vec![json!("x"), json!(3)], vec![json!("x"), json!(4)]];
+
+    let plan = build_plan(&sum_and_avg(), &schema).unwrap();
+    let out = execute_aggregation(&rows, &plan, &schema.col_index).unwrap();
+
+    assert_eq!(out[0][1], json!(7));
+    assert!(out[0][1].is_i64());
+    assert_eq!(out[0][2], json!(3.5));
+}
+
+#[test]
+fn precision_is_configurable_per_column() {
+    let schema = schema(NumberFormat { precision: 3, ..Default::default() });
+    let rows = vec![vec![json!("x"), json!(1.23456)]];
+
+    let plan = build_plan(&sum_and_avg(), &schema).unwrap();
+    let out = execute_aggregation(&rows, &plan, &schema.col_index).unwrap();
+
+    assert_eq!(out[0][1], json!(1.235));
+}
+
+#[test]
+fn values_outside_decimal_range_fall_back_to_float_sums() {
+    let schema = schema(NumberFormat { precision: 2, decimal: true, integer: false });
+    let rows = vec![
+        vec![json!("big"), json!(1e30)],
+        vec![json!("big"), json!(1.0)],
+        vec![json!("max"), json!(7.5e28)],
+        vec![json!("max"), json!(7.5e28)],
+    ];
+
+    let plan = build_plan(&sum_and_avg(), &schema).unwrap();
+    let out = execute_aggregation(&rows, &plan, &schema.col_index).unwrap();
+
+    assert_eq!(out[0][1].as_f64(), Some(1e30));
+    assert_eq!(out[1][1].as_f64(), Some(1.5e29));
+    assert_eq!(out[1][2].as_f64(), Some(7.5e28));
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use luzmo_plugin::engine::aggregation::execute_aggregation;
+use luzmo_plugin::engine::cancel::Cancellation;
+use luzmo_plugin::engine::comparison::apply_comparisons_until;
+use luzmo_plugin::engine::dataset::Schema;
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::engine::plan::build_plan;
+use luzmo_plugin::luzmo::types::{Column, FilterExpr, QueryRequest};
+use serde_json::json;
+
+fn monthly(output: &str, filters: Option<Vec<FilterExpr>>) -> QueryRequest {
+    QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![
+            Column { id: "date".into(), level: Some("month".into()), ..Default::default() },
+            Column { id: "value".into(), aggregation: Some("sum".into()), ..Default::default() },
+            Column {
+                id: "value".into(),
+                aggregation: Some("sum".into()),
+                comparison: Some("previous_period".into()),
+                comparison_output: Some(output.into()),
+                ..Default::default()
+            },
+        ]),
+        filters,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn month_over_month_uses_previous_bucket() {
+    let all = run(&monthly("absolute", None), &QueryContext::default()).unwrap().0;
+    assert!(all[0][2].is_null());
+    let expected = all[1][1].as_f64().unwrap() - all[0][1].as_f64().unwrap();
+    assert!((all[1][2].as_f64().unwrap() - expected).abs() < 0.05);
+}
+
+#[test]
+fn comparison_window_ignores_date_filter() {
+    let unfiltered = run(&monthly("value", None), &QueryContext::default()).unwrap().0;
+
+    let from_march = vec![FilterExpr {
+        column_id: Some("date".into()),
+        id: None,
+        expression: Some(">=".into()),
+        value: Some(json!("2025-03-01T00:00:00.000Z")),
+    }];
+    let rows = run(&monthly("value", Some(from_march)), &QueryContext::default()).unwrap().0;
+
+    assert_eq!(rows[0][0], json!("2025-03-01T00:00:00.000Z"));
+    assert_eq!(rows[0][2], unfiltered[1][1]);
+}
+
+#[test]
+fn comparison_requires_date_grouping() {
+    let req = QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![
+            Column { id: "category".into(), ..Default::default() },
+            Column {
+                id: "value".into(),
+                aggregation: Some("sum".into()),
+                comparison: Some("previous_year".into()),
+                ..Default::default()
+            },
+        ]),
+        ..Default::default()
+    };
+    assert!(run(&req, &QueryContext::default()).is_err());
+}
+
+#[test]
+fn week_level_previous_year_finds_the_matching_week() {
+    let schema = Schema {
+        col_index: HashMap::from([("date".to_string(), 0), ("value".to_string(), 1)]),
+        ..Default::default()
+    };
+    // 2025-01-08 is in the week of Monday 2025-01-06; a year back lands on
+    // Saturday 2024-01-06, in the week of Monday 2024-01-01
+    let data = vec![
+        vec![json!("2024-01-03T00:00:00.000Z"), json!(10.0)],
+        vec![json!("2025-01-08T00:00:00.000Z"), json!(25.0)],
+    ];
+    let req = QueryRequest {
+        columns: Some(vec![
+            Column { id: "date".into(), level: Some("week".into()), ..Default::default() },
+            Column {
+                id: "value".into(),
+                aggregation: Some("sum".into()),
+                comparison: Some("previous_year".into()),
+                comparison_output: Some("absolute".into()),
+                ..Default::default()
+            },
+        ]),
+        ..Default::default()
+    };
+
+    let plan = build_plan(&req, &schema).unwrap();
+    let mut rows = execute_aggregation(&data, &plan, &schema.col_index).unwrap();
+    apply_comparisons_until(&mut rows, &plan, &data, None, &schema, &Cancellation::default()).unwrap();
+
+    assert_eq!(rows[1][0], json!("2025-01-06T00:00:00.000Z"));
+    assert_eq!(rows[1][1], json!(15.0));
+    assert!(rows[0][1].is_null());
+}
@@ -1,3 +1,4 @@
+use luzmo_plugin::engine::context::QueryContext;
 use luzmo_plugin::engine::execute::run;
 use luzmo_plugin::luzmo::types::{Column, QueryRequest};
 
@@ -10,8 +11,8 @@ fn raw_query_returns_rows() {
         ..Default::default()
     };
 
-    let rows = run(&req).unwrap();
-    assert!(rows.len() > 0);
+    let rows = run(&req, &QueryContext::default()).unwrap().0;
+    assert!(!rows.is_empty());
     assert!(rows[0].len() >= 3); // category, date, value
 }
 
@@ -27,8 +28,8 @@ fn non_agg_selects_requested_columns() {
         ..Default::default()
     };
 
-    let rows = run(&req).unwrap();
-    assert!(rows.len() > 0);
+    let rows = run(&req, &QueryContext::default()).unwrap().0;
+    assert!(!rows.is_empty());
     assert_eq!(rows[0].len(), 2);
     assert!(rows[0][0].is_string());
     assert!(rows[0][1].is_string());
@@ -46,7 +47,7 @@ fn agg_query_groups_and_sums() {
         ..Default::default()
     };
 
-    let rows = run(&req).unwrap();
-    assert!(rows.len() > 0);
+    let rows = run(&req, &QueryContext::default()).unwrap().0;
+    assert!(!rows.is_empty());
     assert_eq!(rows[0].len(), 2); // category + sum(value)
 }
\ No newline at end of file
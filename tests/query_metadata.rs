@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{AllowAll, CredentialVerifier};
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
+use serde_json::{json, Value};
+
+async fn query(body: Value) -> (Option<Value>, Value) {
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(AllowAll);
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(Tenants::default()))
+            .app_data(web::Data::new(RowPolicies::default()))
+            .app_data(web::Data::new(SigningConfig::default()))
+            .route("/query", web::post().to(server::query::handle_query)),
+    )
+    .await;
+
+    let res = actix_test::call_service(
+        &app,
+        actix_test::TestRequest::post()
+            .uri("/query")
+            .insert_header(("X-Secret", "dev_secret"))
+            .set_json(body)
+            .to_request(),
+    )
+    .await;
+    assert!(res.status().is_success());
+    let meta = res
+        .headers()
+        .get("X-Query-Metadata")
+        .map(|v| serde_json::from_slice(v.as_bytes()).unwrap());
+    (meta, actix_test::read_body_json(res).await)
+}
+
+#[actix_web::test]
+async fn include_metadata_returns_execution_stats_header() {
+    let (meta, rows) = query(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}],
+        "filters": [{"column_id": "category", "expression": "in", "value": ["A", "B"]}],
+        "options": {"include_metadata": true}
+    }))
+    .await;
+
+    let meta = meta.expect("X-Query-Metadata header");
+    assert_eq!(meta["groups"], 2);
+    assert_eq!(meta["rows_returned"], rows.as_array().unwrap().len());
+    assert!(meta["rows_scanned"].as_u64() > meta["rows_filtered"].as_u64());
+    assert_eq!(meta["pushdown"], false);
+    for phase in ["filter_ms", "plan_ms", "aggregate_ms", "serialize_ms"] {
+        assert!(meta["timings"][phase].is_number(), "{}", phase);
+    }
+}
+
+#[actix_web::test]
+async fn metadata_is_omitted_by_default() {
+    let (meta, rows) = query(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}]
+    }))
+    .await;
+
+    assert!(meta.is_none());
+    assert!(rows.is_array());
+}
@@ -0,0 +1,124 @@
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::body::to_bytes;
+use actix_web::ResponseError;
+use luzmo_plugin::engine::cancel::Cancellation;
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::execute::{run_or_stream, QueryResult};
+use luzmo_plugin::engine::pool::{PoolConfig, QueryPool};
+use luzmo_plugin::errors::PluginError;
+use luzmo_plugin::luzmo::types::QueryRequest;
+use luzmo_plugin::server::stream::JsonArrayBody;
+use luzmo_plugin::utils::credentials::Identity;
+use serde_json::{json, Value};
+
+fn pool(max_concurrent: usize, max_queued: usize) -> Arc<QueryPool> {
+    Arc::new(QueryPool::new(PoolConfig {
+        max_concurrent,
+        max_queued,
+    }))
+}
+
+// Occupies one slot until the returned sender is dropped.
+async fn hold_slot(pool: &Arc<QueryPool>) -> mpsc::Sender<()> {
+    let (tx, rx) = mpsc::channel::<()>();
+    let p = pool.clone();
+    actix_web::rt::spawn(async move { p.run(move || rx.recv()).await });
+    while pool.running() == 0 {
+        actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+    }
+    tx
+}
+
+#[actix_web::test]
+async fn full_pool_without_queue_rejects_with_503() {
+    let pool = pool(1, 0);
+    let release = hold_slot(&pool).await;
+
+    let err = pool.run(|| 1).await.unwrap_err();
+    assert!(matches!(err, PluginError::Overloaded { .. }), "{:?}", err);
+    assert_eq!(err.status_code().as_u16(), 503);
+    assert_eq!(err.error_response().headers().get("retry-after").unwrap(), "1");
+
+    drop(release);
+    while pool.running() > 0 {
+        actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(pool.run(|| 2).await.unwrap(), 2);
+}
+
+#[actix_web::test]
+async fn queued_queries_run_once_a_slot_frees_up() {
+    let pool = pool(1, 1);
+    let release = hold_slot(&pool).await;
+
+    let p = pool.clone();
+    let waiting = actix_web::rt::spawn(async move { p.run(|| "done").await });
+    while pool.queued() == 0 {
+        actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    // the queue is full too
+    assert!(matches!(pool.run(|| "late").await, Err(PluginError::Overloaded { .. })));
+
+    drop(release);
+    assert_eq!(waiting.await.unwrap().unwrap(), "done");
+    assert_eq!(pool.queued(), 0);
+}
+
+#[actix_web::test]
+async fn dropped_waiters_leave_the_queue() {
+    let pool = pool(1, 1);
+    let _release = hold_slot(&pool).await;
+
+    let p = pool.clone();
+    let waiting = actix_web::rt::spawn(async move { p.run(|| ()).await });
+    while pool.queued() == 0 {
+        actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+    }
+    waiting.abort();
+    let _ = waiting.await;
+    assert_eq!(pool.queued(), 0);
+}
+
+#[actix_web::test]
+async fn waiting_for_a_slot_ends_at_the_deadline() {
+    let pool = pool(1, 1);
+    let _release = hold_slot(&pool).await;
+
+    let cancel = Cancellation::with_timeout(Some(Duration::from_millis(50)));
+    let err = pool.run_until(&cancel, || ()).await.unwrap_err();
+    assert!(matches!(err, PluginError::Timeout { .. }), "{:?}", err);
+    assert_eq!(pool.queued(), 0);
+}
+
+#[actix_web::test]
+async fn streamed_body_keeps_the_slot_until_the_last_chunk() {
+    let pool = pool(1, 0);
+    let export: QueryRequest = serde_json::from_value(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}, {"id": "value"}]
+    }))
+    .unwrap();
+
+    let ((result, _), slot) = pool
+        .run_holding(&Cancellation::default(), move || {
+            run_or_stream(&export, &QueryContext::new(Identity::anonymous()), 1).unwrap()
+        })
+        .await
+        .unwrap();
+    let QueryResult::Stream(rows) = result else {
+        panic!("expected a stream");
+    };
+    let body = JsonArrayBody::new(rows, 1024).holding(slot);
+    assert_eq!(pool.running(), 1);
+    assert!(matches!(pool.run(|| ()).await, Err(PluginError::Overloaded { .. })));
+
+    let rows: Vec<Vec<Value>> = serde_json::from_slice(&to_bytes(body).await.unwrap()).unwrap();
+    assert!(!rows.is_empty());
+    while pool.running() > 0 {
+        actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+    }
+}
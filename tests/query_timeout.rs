@@ -0,0 +1,152 @@
+mod common;
+
+use std::future::Future;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use actix_web::dev::Service;
+use actix_web::{test as actix_test, web, App, ResponseError};
+use luzmo_plugin::engine::aggregation::execute_aggregation;
+use luzmo_plugin::engine::cancel::{timeout_for, CancelOnDrop, Cancellation};
+use luzmo_plugin::engine::comparison::apply_comparisons_until;
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::dataset::{demo_rows, demo_schema};
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::engine::plan::build_plan;
+use luzmo_plugin::engine::window::apply_windows_until;
+use luzmo_plugin::errors::PluginError;
+use luzmo_plugin::luzmo::types::QueryRequest;
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{AllowAll, CredentialVerifier};
+use luzmo_plugin::utils::metrics::metrics;
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
+use serde_json::json;
+
+fn sum_by_category() -> QueryRequest {
+    serde_json::from_value(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}]
+    }))
+    .unwrap()
+}
+
+#[test]
+fn expired_deadline_fails_with_gateway_timeout() {
+    let ctx = QueryContext {
+        cancel: Cancellation::with_timeout(Some(Duration::ZERO)),
+        ..Default::default()
+    };
+
+    let err = run(&sum_by_category(), &ctx).unwrap_err();
+    assert!(matches!(err, PluginError::Timeout { .. }), "{:?}", err);
+    assert_eq!(err.status_code().as_u16(), 504);
+    assert_eq!(err.kind(), "Timeout");
+
+    let ok = QueryContext {
+        cancel: Cancellation::with_timeout(Some(Duration::from_secs(60))),
+        ..Default::default()
+    };
+    assert!(run(&sum_by_category(), &ok).is_ok());
+}
+
+#[test]
+fn dropping_the_guard_cancels_unless_disarmed() {
+    let cancel = Cancellation::default();
+    CancelOnDrop::new(&cancel, "unit").disarm();
+    assert!(cancel.check().is_ok());
+
+    drop(CancelOnDrop::new(&cancel, "unit"));
+    assert!(cancel.is_cancelled());
+
+    let ctx = QueryContext { cancel, ..Default::default() };
+    let err = run(&sum_by_category(), &ctx).unwrap_err();
+    assert!(err.to_string().ends_with("Query cancelled"), "{}", err);
+}
+
+#[test]
+fn per_dataset_timeouts_override_the_global_one() {
+    let _env = common::set_env(&[
+        ("LUZMO_PLUGIN_QUERY_TIMEOUT_MS", "10000"),
+        ("LUZMO_PLUGIN_DATASET_TIMEOUTS", "demo=2500, slow=0"),
+    ]);
+
+    assert_eq!(timeout_for("demo"), Some(Duration::from_millis(2500)));
+    assert_eq!(timeout_for("slow"), None);
+    assert_eq!(timeout_for("other"), Some(Duration::from_secs(10)));
+}
+
+#[test]
+fn comparison_and_window_passes_stop_when_cancelled() {
+    let req: QueryRequest = serde_json::from_value(json!({
+        "dataset_id": "demo",
+        "columns": [
+            {"id": "date", "level": "month"},
+            {"id": "value", "aggregation": "sum", "comparison": "previous_period"},
+            {"id": "value", "aggregation": "sum", "window": "running_sum"}
+        ]
+    }))
+    .unwrap();
+    let schema = demo_schema();
+    let data = demo_rows();
+    let plan = build_plan(&req, &schema).unwrap();
+    let mut rows = execute_aggregation(&data, &plan, &schema.col_index).unwrap();
+
+    let cancel = Cancellation::default();
+    cancel.cancel();
+    let err = apply_comparisons_until(&mut rows, &plan, &data, None, &schema, &cancel).unwrap_err();
+    assert!(matches!(err, PluginError::Timeout { .. }), "{:?}", err);
+    let err = apply_windows_until(&mut rows, &plan, &cancel).unwrap_err();
+    assert!(matches!(err, PluginError::Timeout { .. }), "{:?}", err);
+}
+
+fn cancelled_total() -> f64 {
+    metrics()
+        .render()
+        .lines()
+        .find_map(|l| l.strip_prefix("luzmo_plugin_queries_cancelled_total{dataset=\"demo\"} "))
+        .map_or(0.0, |v| v.parse().unwrap())
+}
+
+// the guard is held across awaits, fine with a runtime per test
+#[allow(clippy::await_holding_lock)]
+#[actix_web::test]
+async fn client_disconnect_cancels_the_running_query() {
+    let _env = common::read_env();
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(AllowAll);
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(Tenants::default()))
+            .app_data(web::Data::new(RowPolicies::default()))
+            .app_data(web::Data::new(SigningConfig::default()))
+            .route("/query", web::post().to(server::query::handle_query)),
+    )
+    .await;
+    let before = cancelled_total();
+
+    // a filter nobody else uses keeps it out of the result cache
+    let req = actix_test::TestRequest::post()
+        .uri("/query")
+        .insert_header(("X-Secret", "dev_secret"))
+        .set_json(json!({
+            "dataset_id": "demo",
+            "columns": [
+                {"id": "date", "level": "day"},
+                {"id": "category"},
+                {"id": "value", "aggregation": "sum", "comparison": "previous_year"}
+            ],
+            "filters": [{"column_id": "customer", "expression": "!=", "value": "client-disconnect-test"}]
+        }))
+        .to_request();
+
+    // de eerste poll geeft de query aan de pool; daarna haakt de client af
+    let mut call = Box::pin(app.call(req));
+    let first = std::future::poll_fn(|cx| Poll::Ready(call.as_mut().poll(cx))).await;
+    assert!(first.is_pending());
+    drop(call);
+
+    assert_eq!(cancelled_total(), before + 1.0);
+}
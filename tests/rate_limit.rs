@@ -0,0 +1,178 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::middleware::from_fn;
+use actix_web::{test as actix_test, web, App, ResponseError};
+use chrono::{TimeZone, Utc};
+use luzmo_plugin::errors::PluginError;
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{AllowAll, CredentialVerifier};
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::ratelimit::{BucketConfig, RateLimitConfig, RateLimiter};
+use luzmo_plugin::utils::signing::{sign, SignedRequest, SigningConfig};
+use luzmo_plugin::utils::tenants::Tenants;
+use serde_json::{json, Value};
+
+fn retry_after(err: PluginError) -> u64 {
+    match err {
+        PluginError::RateLimited { retry_after, .. } => retry_after,
+        other => panic!("expected RateLimited, got {:?}", other),
+    }
+}
+
+#[test]
+fn buckets_refill_and_datasets_are_limited_separately() {
+    let limiter = RateLimiter::new(RateLimitConfig {
+        client: Some(BucketConfig { per_sec: 1.0, burst: 3.0 }),
+        dataset: Some(BucketConfig { per_sec: 0.5, burst: 1.0 }),
+        ..Default::default()
+    });
+    let t0 = Instant::now();
+    let utc = Utc::now();
+
+    assert!(limiter.check("tenant:a", Some("demo"), t0, utc).is_ok());
+    // same dataset again: its bucket is empty for two seconds
+    assert_eq!(retry_after(limiter.check("tenant:a", Some("demo"), t0, utc).unwrap_err()), 2);
+    assert!(limiter.check("tenant:a", Some("other"), t0, utc).is_ok());
+    // the client bucket (burst 3) is now empty too
+    let err = limiter.check("tenant:a", None, t0, utc).unwrap_err();
+    assert_eq!(err.status_code().as_u16(), 429);
+    assert_eq!(err.error_response().headers().get("retry-after").unwrap(), "1");
+
+    assert!(limiter.check("tenant:b", Some("demo"), t0, utc).is_ok());
+    assert!(limiter.check("tenant:a", Some("demo"), t0 + Duration::from_secs(2), utc).is_ok());
+}
+
+#[test]
+fn daily_row_quota_resets_at_utc_midnight() {
+    let limiter = RateLimiter::new(RateLimitConfig {
+        daily_rows: Some(100),
+        ..Default::default()
+    });
+    let now = Instant::now();
+    let evening = Utc.with_ymd_and_hms(2025, 3, 1, 23, 0, 0).unwrap();
+
+    assert!(limiter.check("tenant:a", Some("demo"), now, evening).is_ok());
+    limiter.record_rows("tenant:a", 120, evening);
+    assert_eq!(limiter.rows_used("tenant:a", evening.date_naive()), 120);
+
+    assert_eq!(retry_after(limiter.check("tenant:a", Some("demo"), now, evening).unwrap_err()), 3600);
+    assert!(limiter.check("tenant:b", Some("demo"), now, evening).is_ok());
+
+    let next_day = Utc.with_ymd_and_hms(2025, 3, 2, 0, 0, 1).unwrap();
+    assert!(limiter.check("tenant:a", Some("demo"), now, next_day).is_ok());
+}
+
+#[actix_web::test]
+async fn middleware_rejects_with_retry_after_and_luzmo_error_body() {
+    let limiter = web::Data::new(RateLimiter::new(RateLimitConfig {
+        client: Some(BucketConfig { per_sec: 0.1, burst: 2.0 }),
+        dataset: Some(BucketConfig { per_sec: 0.1, burst: 10.0 }),
+        ..Default::default()
+    }));
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(AllowAll);
+    let app = actix_test::init_service(
+        App::new()
+            .wrap(from_fn(server::middleware::rate_limit))
+            .app_data(limiter.clone())
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(Tenants::default()))
+            .app_data(web::Data::new(RowPolicies::default()))
+            .app_data(web::Data::new(SigningConfig {
+                key: Some(b"rate_limit_key".to_vec()),
+                ..SigningConfig::default()
+            }))
+            .route("/query", web::post().to(server::query::handle_query)),
+    )
+    .await;
+    let query = || {
+        actix_test::TestRequest::post()
+            .uri("/query")
+            .insert_header(("X-Secret", "dev_secret"))
+            .set_json(json!({"dataset_id": "demo", "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}]}))
+            .to_request()
+    };
+
+    for _ in 0..2 {
+        let res = actix_test::call_service(&app, query()).await;
+        assert_eq!(res.status(), 200);
+        let rows: Value = actix_test::read_body_json(res).await;
+        assert_eq!(rows.as_array().unwrap().len(), 6);
+    }
+
+    let res = actix_test::call_service(&app, query()).await;
+    assert_eq!(res.status(), 429);
+    assert!(res.headers().get("Retry-After").is_some());
+    let body: Value = actix_test::read_body_json(res).await;
+    assert_eq!(body["type"]["code"], 429);
+    assert_eq!(body["type"]["description"], "Too many requests");
+    // client and demo dataset bucket for the secret
+    assert_eq!(limiter.len(), 2);
+
+    // signed requests have no X-Secret; they count against the signing key
+    let signed = |dataset_id: &str, ts: i64, key: &[u8]| {
+        let body = json!({"dataset_id": dataset_id, "columns": [{"id": "category"}]}).to_string();
+        let signature = sign(
+            key,
+            ts,
+            &SignedRequest { method: "POST", path: "/query", query: "", body: body.as_bytes() },
+        );
+        actix_test::TestRequest::post()
+            .uri("/query")
+            .insert_header(("X-Timestamp", ts.to_string()))
+            .insert_header(("X-Signature", signature))
+            .insert_header(("Content-Type", "application/json"))
+            .set_payload(body)
+            .to_request()
+    };
+    let now = Utc::now().timestamp();
+
+    // unknown datasets get no bucket of their own
+    let res = actix_test::call_service(&app, signed("nope", now, b"rate_limit_key")).await;
+    assert_eq!(res.status(), 404);
+    assert_eq!(limiter.len(), 3);
+    let res = actix_test::call_service(&app, signed("demo", now, b"rate_limit_key")).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(limiter.len(), 4);
+    let res = actix_test::call_service(&app, signed("demo", now - 1, b"rate_limit_key")).await;
+    assert_eq!(res.status(), 429);
+
+    // a bad signature is not limited here, authentication rejects it
+    let res = actix_test::call_service(&app, signed("demo", now, b"wrong_key")).await;
+    assert_eq!(res.status(), 401);
+}
+
+#[test]
+fn refilled_buckets_are_evicted() {
+    let limiter = RateLimiter::new(RateLimitConfig {
+        client: Some(BucketConfig { per_sec: 1.0, burst: 3.0 }),
+        dataset: Some(BucketConfig { per_sec: 1.0, burst: 3.0 }),
+        ..Default::default()
+    });
+    let t0 = Instant::now();
+    let utc = Utc::now();
+
+    assert!(limiter.check("tenant:a", Some("demo"), t0, utc).is_ok());
+    assert!(limiter.check("tenant:b", None, t0, utc).is_ok());
+    assert_eq!(limiter.len(), 3);
+
+    // full again after three seconds: dropped on the next request
+    assert!(limiter.check("tenant:c", None, t0 + Duration::from_secs(20), utc).is_ok());
+    assert_eq!(limiter.len(), 1);
+}
+
+#[test]
+fn row_counters_only_keep_the_current_day() {
+    let limiter = RateLimiter::new(RateLimitConfig {
+        daily_rows: Some(100),
+        ..Default::default()
+    });
+    let day1 = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+    let day2 = Utc.with_ymd_and_hms(2025, 3, 2, 12, 0, 0).unwrap();
+
+    limiter.record_rows("tenant:a", 10, day1);
+    limiter.record_rows("tenant:b", 20, day2);
+    assert_eq!(limiter.rows_used("tenant:a", day1.date_naive()), 0);
+    assert_eq!(limiter.rows_used("tenant:a", day2.date_naive()), 0);
+    assert_eq!(limiter.rows_used("tenant:b", day2.date_naive()), 20);
+}
@@ -0,0 +1,51 @@
+use std::collections::HashSet;
+
+use actix_web::middleware::from_fn;
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::errors::PluginError;
+use luzmo_plugin::server;
+use luzmo_plugin::utils::ids::{encode_ulid, make_req_id};
+use serde_json::Value;
+
+#[test]
+fn request_ids_are_unique_and_sortable() {
+    let ids: Vec<String> = (0..10_000).map(|_| make_req_id()).collect();
+
+    assert!(ids.iter().all(|id| id.len() == 26));
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(sorted, ids);
+}
+
+#[test]
+fn ulid_encoding_puts_time_first() {
+    assert_eq!(encode_ulid(0, 0), "00000000000000000000000000");
+    assert_eq!(encode_ulid(1, 0), "00000000010000000000000000");
+    assert_eq!(encode_ulid(0, 31), "0000000000000000000000000Z");
+    assert!(encode_ulid(2, 0) > encode_ulid(1, u128::MAX));
+}
+
+async fn fails() -> Result<String, PluginError> {
+    Err(PluginError::InvalidRequest {
+        message: "nope".into(),
+    })
+}
+
+#[actix_web::test]
+async fn error_bodies_carry_the_request_id() {
+    let app = actix_test::init_service(
+        App::new()
+            .wrap(from_fn(server::middleware::request_id))
+            .route("/fails", web::get().to(fails)),
+    )
+    .await;
+
+    let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/fails").to_request()).await;
+    assert_eq!(res.status(), 400);
+    let rid = res.headers().get("X-Request-Id").unwrap().to_str().unwrap().to_string();
+    let body: Value = actix_test::read_body_json(res).await;
+    assert_eq!(body["request_id"], rid);
+    assert_eq!(body["message"], "nope");
+    assert_eq!(body["type"]["code"], 400);
+}
@@ -0,0 +1,89 @@
+use luzmo_plugin::utils::signing::{sign, verify_signature, ReplayGuard, SignedRequest, SigningConfig};
+
+const KEY: &[u8] = b"shared-signing-key";
+const BODY: &[u8] = br#"{"id":"demo","columns":[]}"#;
+
+fn query(body: &[u8]) -> SignedRequest<'_> {
+    SignedRequest {
+        method: "POST",
+        path: "/query",
+        query: "",
+        body,
+    }
+}
+
+#[test]
+fn valid_signature_is_accepted_once() {
+    let now = 1_760_000_000;
+    let sig = sign(KEY, now, &query(BODY));
+    let replays = ReplayGuard::default();
+
+    assert!(verify_signature(KEY, &now.to_string(), &sig, &query(BODY), now + 5, 300, &replays).is_ok());
+    let err = verify_signature(KEY, &now.to_string(), &sig, &query(BODY), now + 6, 300, &replays).unwrap_err();
+    assert_eq!(err, "Replayed X-Signature");
+}
+
+#[test]
+fn tampered_body_or_wrong_key_is_rejected() {
+    let now = 1_760_000_000;
+    let sig = sign(KEY, now, &query(BODY));
+    let replays = ReplayGuard::default();
+
+    let tampered = br#"{"id":"other","columns":[]}"#;
+    assert!(verify_signature(KEY, &now.to_string(), &sig, &query(tampered), now, 300, &replays).is_err());
+    assert!(verify_signature(b"other-key", &now.to_string(), &sig, &query(BODY), now, 300, &replays).is_err());
+    // timestamp is part of the signed payload
+    assert!(verify_signature(KEY, &(now + 1).to_string(), &sig, &query(BODY), now, 300, &replays).is_err());
+    assert!(verify_signature(KEY, &now.to_string(), "not-hex", &query(BODY), now, 300, &replays).is_err());
+}
+
+#[test]
+fn timestamps_outside_the_window_are_rejected() {
+    let ts = 1_760_000_000;
+    let sig = sign(KEY, ts, &query(BODY));
+    let replays = ReplayGuard::default();
+
+    let err = verify_signature(KEY, &ts.to_string(), &sig, &query(BODY), ts + 301, 300, &replays).unwrap_err();
+    assert_eq!(err, "X-Timestamp outside the allowed window");
+    assert!(verify_signature(KEY, &ts.to_string(), &sig, &query(BODY), ts - 301, 300, &replays).is_err());
+    assert!(verify_signature(KEY, "yesterday", &sig, &query(BODY), ts, 300, &replays).is_err());
+}
+
+#[test]
+fn signature_is_bound_to_method_and_path() {
+    let now = 1_760_000_000;
+    let sig = sign(KEY, now, &query(BODY));
+    let replays = ReplayGuard::default();
+
+    let explain = SignedRequest {
+        path: "/query/explain",
+        ..query(BODY)
+    };
+    assert!(verify_signature(KEY, &now.to_string(), &sig, &explain, now, 300, &replays).is_err());
+    let get = SignedRequest {
+        method: "GET",
+        ..query(BODY)
+    };
+    assert!(verify_signature(KEY, &now.to_string(), &sig, &get, now, 300, &replays).is_err());
+    let with_query = SignedRequest {
+        query: "tenant=other",
+        ..query(BODY)
+    };
+    assert!(verify_signature(KEY, &now.to_string(), &sig, &with_query, now, 300, &replays).is_err());
+    assert!(verify_signature(KEY, &now.to_string(), &sig, &query(BODY), now, 300, &replays).is_ok());
+}
+
+#[test]
+fn required_signing_needs_a_key() {
+    let cfg = SigningConfig {
+        key: None,
+        required: true,
+        window: 300,
+    };
+    assert!(cfg.validate().is_err());
+    let cfg = SigningConfig {
+        key: Some(KEY.to_vec()),
+        ..cfg
+    };
+    assert!(cfg.validate().is_ok());
+}
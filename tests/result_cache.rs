@@ -0,0 +1,143 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use luzmo_plugin::engine::cache::{cache_key, CacheConfig, ResultCache, Rows};
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::dataset::{dataset_version, mark_dataset_reloaded};
+use luzmo_plugin::engine::execute::{run_or_stream, QueryResult};
+use luzmo_plugin::engine::stats::QueryStats;
+use luzmo_plugin::luzmo::types::QueryRequest;
+use luzmo_plugin::utils::credentials::Identity;
+use serde_json::json;
+
+fn request(body: serde_json::Value) -> QueryRequest {
+    serde_json::from_value(body).unwrap()
+}
+
+fn ctx(user: &str, roles: &[&str]) -> QueryContext {
+    QueryContext::new(Identity {
+        user: user.into(),
+        roles: roles.iter().map(|r| r.to_string()).collect(),
+        ..Default::default()
+    })
+}
+
+// Through the result cache, never streamed.
+fn run_cached(req: &QueryRequest, ctx: &QueryContext) -> (Rows, QueryStats) {
+    match run_or_stream(req, ctx, usize::MAX).unwrap() {
+        (QueryResult::Rows(rows), stats) => (rows, stats),
+        (QueryResult::Stream(_), _) => panic!("expected materialized rows"),
+    }
+}
+
+fn small_cache(max_entries: usize, ttl_secs: u64) -> ResultCache {
+    ResultCache::new(CacheConfig {
+        max_entries,
+        max_bytes: 1024 * 1024,
+        ttl: Duration::from_secs(ttl_secs),
+    })
+}
+
+#[test]
+fn equivalent_queries_share_a_key_per_identity() {
+    let a = request(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}],
+        "filters": [{"column_id": "category", "expression": "=", "value": ["A"]}]
+    }));
+    let b = request(json!({
+        "id": "demo",
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}],
+        "filters": [{"expression": "equal", "value": ["A"], "column_id": "category"}]
+    }));
+
+    let alice = ctx("alice", &["b", "a"]);
+    assert_eq!(cache_key(&a, &alice), cache_key(&b, &ctx("alice", &["a", "b"])));
+    assert_ne!(cache_key(&a, &alice), cache_key(&a, &ctx("bob", &["a", "b"])));
+    assert_ne!(cache_key(&a, &alice), cache_key(&a, &ctx("alice", &["admin"])));
+}
+
+#[test]
+fn column_spelling_is_normalized_and_row_filters_are_keyed() {
+    let a = request(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}]
+    }));
+    let b = request(json!({
+        "dataset_id": "demo",
+        "columns": [{"column_id": "category"}, {"column_id": "value", "aggregation": "SUM"}]
+    }));
+    let alice = ctx("alice", &[]);
+    assert_eq!(cache_key(&a, &alice), cache_key(&b, &alice));
+    assert_eq!(
+        run_cached(&a, &alice).0,
+        run_cached(&b, &alice).0
+    );
+
+    let mut restricted = ctx("alice", &[]);
+    restricted.row_filters = serde_json::from_value(json!([
+        {"column_id": "category", "expression": "=", "value": ["A"]}
+    ]))
+    .unwrap();
+    assert_ne!(cache_key(&a, &alice), cache_key(&a, &restricted));
+}
+
+#[test]
+fn lru_entries_expire_and_are_evicted() {
+    let cache = small_cache(2, 60);
+    let t0 = Instant::now();
+    let rows = Arc::new(vec![vec![json!("A"), json!(1)]]);
+
+    cache.insert("k1".into(), "demo", rows.clone(), QueryStats::default(), t0);
+    cache.insert("k2".into(), "demo", rows.clone(), QueryStats::default(), t0);
+    assert!(cache.get("k1", t0).is_some()); // k2 is now least recently used
+    cache.insert("k3".into(), "demo", rows.clone(), QueryStats::default(), t0);
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("k2", t0).is_none());
+    assert!(cache.get("k1", t0).is_some());
+    assert!(cache.get("k1", t0 + Duration::from_secs(61)).is_none());
+
+    cache.invalidate_dataset("demo");
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn results_larger_than_the_byte_limit_are_not_cached() {
+    let cache = ResultCache::new(CacheConfig {
+        max_entries: 8,
+        max_bytes: 1024,
+        ttl: Duration::from_secs(60),
+    });
+    let t0 = Instant::now();
+    let small = Arc::new(vec![vec![json!("A"), json!(1)]]);
+    let large = Arc::new(vec![vec![json!("x".repeat(2048))]]);
+
+    cache.insert("small".into(), "demo", small, QueryStats::default(), t0);
+    cache.insert("large".into(), "demo", large, QueryStats::default(), t0);
+    assert!(cache.get("small", t0).is_some());
+    assert!(cache.get("large", t0).is_none());
+}
+
+#[test]
+fn repeated_queries_hit_the_cache_until_the_dataset_reloads() {
+    let req = request(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}]
+    }));
+    let alice = ctx("cache-test", &[]);
+
+    let (first, stats) = run_cached(&req, &alice);
+    assert!(!stats.cache_hit);
+    let (second, stats) = run_cached(&req, &alice);
+    assert!(stats.cache_hit);
+    assert_eq!(stats.timings.filter_ms, 0.0);
+    assert_eq!(stats.timings.aggregate_ms, 0.0);
+    assert_eq!(first, second);
+
+    let before = dataset_version("demo");
+    assert_eq!(mark_dataset_reloaded("demo"), before + 1);
+    let (_, stats) = run_cached(&req, &alice);
+    assert!(!stats.cache_hit);
+}
@@ -0,0 +1,89 @@
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::luzmo::types::{Column, FilterExpr, QueryRequest};
+use luzmo_plugin::utils::credentials::Identity;
+use luzmo_plugin::utils::policies::{RowPolicies, RowPolicy};
+use serde_json::{json, Value};
+
+fn filter(col: &str, op: &str, value: Value) -> FilterExpr {
+    FilterExpr {
+        column_id: Some(col.into()),
+        id: None,
+        expression: Some(op.into()),
+        value: Some(value),
+    }
+}
+
+fn policies() -> RowPolicies {
+    RowPolicies::new(vec![RowPolicy {
+        user: Some("alice".into()),
+        tenant: None,
+        role: None,
+        dataset: Some("demo".into()),
+        filters: vec![filter("category", "in", json!(["A", "B"]))],
+    }])
+}
+
+fn alice() -> QueryContext {
+    let identity = Identity { user: "alice".into(), ..Default::default() };
+    QueryContext {
+        row_filters: policies().filters_for(&identity, "demo").unwrap(),
+        identity,
+        ..Default::default()
+    }
+}
+
+fn by_category(filters: Option<Vec<FilterExpr>>) -> QueryRequest {
+    QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![
+            Column { id: "category".into(), ..Default::default() },
+            Column { id: "value".into(), aggregation: Some("sum".into()), ..Default::default() },
+        ]),
+        filters,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn policies_match_on_identity_and_dataset() {
+    let bob = Identity { user: "bob".into(), ..Default::default() };
+    // no policy for bob: no access, rather than no filters
+    assert!(policies().filters_for(&bob, "demo").is_none());
+    assert!(RowPolicies::default().filters_for(&bob, "demo").is_some_and(|f| f.is_empty()));
+    assert_eq!(alice().row_filters.len(), 1);
+}
+
+#[test]
+fn policy_filters_without_value_are_rejected() {
+    let dir = std::env::temp_dir().join(format!("policies-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("policies.json");
+    std::fs::write(&path, r#"[{ "user": "alice", "filters": [{ "column_id": "category", "expression": "in" }] }]"#)
+        .unwrap();
+    assert!(RowPolicies::from_file(path.to_str().unwrap()).is_err());
+
+    let no_value = FilterExpr { value: None, ..filter("category", "in", json!(null)) };
+    let open = QueryContext { row_filters: vec![no_value.clone()], ..alice() };
+    assert!(run(&by_category(None), &open).is_err());
+
+    // in a request it is skipped, as before
+    let rows = run(&by_category(Some(vec![no_value])), &alice()).unwrap().0;
+    assert_eq!(rows, run(&by_category(None), &alice()).unwrap().0);
+}
+
+#[test]
+fn row_filters_restrict_results() {
+    let rows = run(&by_category(None), &alice()).unwrap().0;
+    let cats: Vec<_> = rows.iter().map(|r| r[0].clone()).collect();
+    assert_eq!(cats, vec![json!("A"), json!("B")]);
+}
+
+#[test]
+fn request_filters_cannot_widen_policy() {
+    let wider = vec![filter("category", "in", json!(["A", "B", "C", "D"]))];
+    assert_eq!(run(&by_category(Some(wider)), &alice()).unwrap().0.len(), 2);
+
+    let outside = vec![filter("category", "=", json!("C"))];
+    assert!(run(&by_category(Some(outside)), &alice()).unwrap().0.is_empty());
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use luzmo_plugin::utils::secret::{parse_secret_lines, SecretEntry, SecretStore};
+
+#[test]
+fn parses_secrets_with_optional_expiry() {
+    let entries = parse_secret_lines(
+        "# rotated 2026-10\nnew_secret\n\nold_secret 2026-11-01T00:00:00Z\n",
+    )
+    .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0], SecretEntry::new("new_secret"));
+    assert_eq!(entries[1].value, "old_secret");
+    assert!(entries[1].expires_at.is_some());
+}
+
+#[test]
+fn current_and_previous_secrets_are_both_valid_until_expiry() {
+    let now = Utc::now();
+    let store = SecretStore::new(vec![
+        SecretEntry::new("current"),
+        SecretEntry {
+            value: "previous".into(),
+            expires_at: Some(now + Duration::hours(1)),
+        },
+    ]);
+
+    assert!(store.is_valid("current", now));
+    assert!(store.is_valid("previous", now));
+    assert!(!store.is_valid("previous", now + Duration::hours(2)));
+    assert!(!store.is_valid("other", now));
+    assert!(!store.is_valid("", now));
+}
+
+#[test]
+fn bad_expiry_or_unreadable_source_is_an_error() {
+    assert!(parse_secret_lines("old_secret 2026-13-01").is_err());
+    assert!(parse_secret_lines("old_secret 2026-11-01T00:00:00Z extra").is_err());
+
+    // a configured but missing file must not fall back to dev_secret
+    let _env = common::set_env(&[("LUZMO_PLUGIN_SECRET_FILE", "/nonexistent/luzmo-secret")]);
+    assert!(SecretStore::load().is_err());
+}
+
+#[test]
+fn env_secrets_are_taken_verbatim() {
+    let _env = common::set_env(&[
+        ("LUZMO_PLUGIN_SECRET", "#not a comment"),
+        ("LUZMO_PLUGIN_SECRET_PREVIOUS", "old secret with spaces"),
+        ("LUZMO_PLUGIN_SECRET_PREVIOUS_EXPIRES", "2099-01-01T00:00:00Z"),
+    ]);
+    let store = SecretStore::load().unwrap();
+    assert!(store.is_valid("#not a comment", Utc::now()));
+    assert!(store.is_valid("old secret with spaces", Utc::now()));
+    assert!(!store.is_valid("old", Utc::now()));
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::PathBuf;
+
+use luzmo_plugin::engine::stats::QueryStats;
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::slowlog::{read_entries, replay, SlowLogConfig, SlowQueryEntry, SlowQueryLog};
+use serde_json::{json, Value};
+
+fn temp_log(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("luzmo-slowlog-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("slow.jsonl")
+}
+
+fn entry(request_id: &str) -> SlowQueryEntry {
+    SlowQueryEntry {
+        ts: "2026-10-19T12:00:00Z".into(),
+        request_id: request_id.into(),
+        dataset_id: "demo".into(),
+        user: "alice".into(),
+        tenant: None,
+        roles: vec![],
+        elapsed_ms: 1234.5,
+        body: json!({
+            "dataset_id": "demo",
+            "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}]
+        }),
+        redacted: false,
+        plan: Value::Null,
+        stats: QueryStats::default(),
+    }
+}
+
+#[test]
+fn entries_are_appended_and_read_back() {
+    let path = temp_log("append");
+    let log = SlowQueryLog::new(SlowLogConfig {
+        path: path.clone(),
+        threshold_ms: 500.0,
+        max_bytes: 1024 * 1024,
+        keep: 2,
+    });
+
+    assert!(!log.is_slow(499.0));
+    assert!(log.is_slow(500.0));
+    log.record(&entry("a")).unwrap();
+    log.record(&entry("b")).unwrap();
+
+    let entries = read_entries(&path).unwrap();
+    assert_eq!(entries.iter().map(|e| e.request_id.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+    assert_eq!(entries[0].body["columns"][1]["aggregation"], "sum");
+}
+
+#[test]
+fn log_rotates_and_keeps_a_bounded_number_of_files() {
+    let path = temp_log("rotate");
+    let line_len = serde_json::to_string(&entry("x")).unwrap().len() as u64 + 1;
+    let log = SlowQueryLog::new(SlowLogConfig {
+        path: path.clone(),
+        threshold_ms: 0.0,
+        max_bytes: line_len * 2,
+        keep: 2,
+    });
+
+    for id in ["1", "2", "3", "4", "5", "6", "7"] {
+        log.record(&entry(id)).unwrap();
+    }
+
+    let ids = |p: PathBuf| read_entries(&p).unwrap().into_iter().map(|e| e.request_id).collect::<Vec<_>>();
+    assert_eq!(ids(path.clone()), ["7"]);
+    assert_eq!(ids(path.with_extension("jsonl.1")), ["5", "6"]);
+    assert_eq!(ids(path.with_extension("jsonl.2")), ["3", "4"]);
+    assert!(!path.with_extension("jsonl.3").exists());
+}
+
+#[test]
+fn recorded_queries_replay_offline() {
+    let result = replay(&entry("r"), &RowPolicies::default()).unwrap();
+    assert!(result.rows > 0);
+    assert_eq!(result.stats.groups, result.rows);
+
+    let mut broken = entry("broken");
+    broken.body = json!({"dataset_id": "demo", "columns": [{"id": "nope"}]});
+    assert!(replay(&broken, &RowPolicies::default()).is_err());
+}
+
+#[test]
+fn redacted_bodies_are_marked_and_not_replayed() {
+    let log = SlowQueryLog::new(SlowLogConfig {
+        path: temp_log("redacted"),
+        threshold_ms: 0.0,
+        max_bytes: 1024,
+        keep: 1,
+    });
+
+    let (body, redacted) = log.redact_body(&entry("plain").body);
+    assert!(!redacted);
+    assert_eq!(body, entry("plain").body);
+
+    let mut secret = entry("secret");
+    let (body, redacted) = log.redact_body(&json!({"dataset_id": "demo", "token": "t0p", "columns": []}));
+    assert!(redacted);
+    assert_eq!(body["token"], "[REDACTED]");
+
+    secret.body = body;
+    secret.redacted = redacted;
+    let err = replay(&secret, &RowPolicies::default()).unwrap_err();
+    assert!(err.to_string().contains("redacted"));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use actix_web::body::{to_bytes, MessageBody};
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::execute::{run, run_or_stream, QueryResult};
+use luzmo_plugin::luzmo::types::QueryRequest;
+use luzmo_plugin::server;
+use luzmo_plugin::server::stream::{JsonArrayBody, StreamEnd};
+use luzmo_plugin::utils::credentials::{AllowAll, CredentialVerifier, Identity};
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::Tenants;
+use serde_json::{json, Value};
+
+fn raw_export() -> QueryRequest {
+    serde_json::from_value(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "date", "level": "month"}, {"id": "category", "level": "division"}, {"id": "value"}]
+    }))
+    .unwrap()
+}
+
+fn stream_of(req: &QueryRequest, min_rows: usize) -> JsonArrayBody {
+    match run_or_stream(req, &QueryContext::new(Identity::anonymous()), min_rows).unwrap() {
+        (QueryResult::Stream(rows), _) => JsonArrayBody::new(rows, 1024),
+        (QueryResult::Rows(_), _) => panic!("expected a stream"),
+    }
+}
+
+#[actix_web::test]
+async fn streamed_body_matches_the_materialized_result() {
+    let expected = run(&raw_export(), &QueryContext::new(Identity::anonymous())).unwrap().0;
+
+    let mut body = stream_of(&raw_export(), 1);
+    let first = std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_next(cx))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(first.len() < 2048, "chunks stay near the chunk size");
+
+    let rest = to_bytes(body).await.unwrap();
+    let streamed: Vec<Vec<Value>> = serde_json::from_slice(&[first, rest].concat()).unwrap();
+    assert_eq!(streamed, expected);
+}
+
+#[actix_web::test]
+async fn small_and_aggregated_results_are_not_streamed() {
+    let ctx = QueryContext::new(Identity::anonymous());
+    let mut limited = raw_export();
+    limited.limit = Some(50);
+    let (small, _) = run_or_stream(&limited, &ctx, 100).unwrap();
+    assert!(matches!(small, QueryResult::Rows(_)));
+
+    let agg: QueryRequest = serde_json::from_value(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}, {"id": "value", "aggregation": "sum"}]
+    }))
+    .unwrap();
+    let (agg, _) = run_or_stream(&agg, &ctx, 1).unwrap();
+    assert!(matches!(agg, QueryResult::Rows(_)));
+
+    let empty: QueryRequest = serde_json::from_value(json!({
+        "dataset_id": "demo",
+        "columns": [{"id": "category"}],
+        "limit": 0
+    }))
+    .unwrap();
+    assert_eq!(to_bytes(stream_of(&empty, 0)).await.unwrap(), "[]");
+}
+
+fn outcome(end: StreamEnd<'_>) -> String {
+    match end {
+        StreamEnd::Complete(sent) => format!("complete {}", sent),
+        StreamEnd::Failed(e) => format!("failed: {}", e),
+        StreamEnd::Disconnected { sent } => format!("disconnected after {}", sent),
+    }
+}
+
+#[actix_web::test]
+async fn stream_reports_its_end_and_stops_at_the_deadline() {
+    let finished: Arc<Mutex<Vec<String>>> = Arc::default();
+    let report = finished.clone();
+    let body = stream_of(&raw_export(), 1).on_finish(move |end| report.lock().unwrap().push(outcome(end)));
+    let rows: Vec<Vec<Value>> = serde_json::from_slice(&to_bytes(body).await.unwrap()).unwrap();
+    assert_eq!(*finished.lock().unwrap(), [format!("complete {}", rows.len())]);
+
+    // de deadline verstrijkt terwijl er nog rijen komen
+    let ctx = QueryContext::new(Identity::anonymous());
+    let (result, _) = run_or_stream(&raw_export(), &ctx, 1).unwrap();
+    let QueryResult::Stream(rows) = result else {
+        panic!("expected a stream");
+    };
+    ctx.cancel.cancel();
+    let report = finished.clone();
+    let body = JsonArrayBody::new(rows, 1024).on_finish(move |end| report.lock().unwrap().push(outcome(end)));
+    let err = to_bytes(body).await.unwrap_err();
+    assert!(err.to_string().ends_with("Query cancelled"), "{}", err);
+    assert!(finished.lock().unwrap()[1].starts_with("failed: "));
+
+    // the client goes away before the body is read
+    let report = finished.clone();
+    drop(stream_of(&raw_export(), 1).on_finish(move |end| report.lock().unwrap().push(outcome(end))));
+    assert_eq!(finished.lock().unwrap()[2], "disconnected after 0");
+}
+
+#[actix_web::test]
+async fn query_endpoint_streams_large_raw_results() {
+    let _env = common::set_env(&[("LUZMO_PLUGIN_STREAM_MIN_ROWS", "100")]);
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(AllowAll);
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(Tenants::default()))
+            .app_data(web::Data::new(RowPolicies::default()))
+            .app_data(web::Data::new(SigningConfig::default()))
+            .route("/query", web::post().to(server::query::handle_query)),
+    )
+    .await;
+
+    let res = actix_test::call_service(
+        &app,
+        actix_test::TestRequest::post()
+            .uri("/query")
+            .insert_header(("X-Secret", "dev_secret"))
+            .set_json(json!({"dataset_id": "demo", "columns": [{"id": "category"}, {"id": "value"}]}))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), 200);
+    assert!(res.headers().get("ETag").is_some());
+    let rows: Vec<Vec<Value>> = actix_test::read_body_json(res).await;
+    assert!(rows.len() >= 100);
+}
@@ -0,0 +1,62 @@
+use actix_web::middleware::from_fn;
+use actix_web::{test as actix_test, web, App, HttpResponse};
+use luzmo_plugin::server;
+use luzmo_plugin::utils::ids::accept_request_id;
+use luzmo_plugin::utils::logging::json_line;
+
+#[test]
+fn log_records_become_json_with_fields() {
+    let kvs: [(&str, log::kv::Value); 3] = [
+        ("request_id", "req-1".into()),
+        ("rows_out", 42u64.into()),
+        ("cached", false.into()),
+    ];
+    let line = json_line(
+        &log::Record::builder()
+            .args(format_args!("query done"))
+            .level(log::Level::Info)
+            .target("luzmo_plugin::server::query")
+            .key_values(&kvs)
+            .build(),
+    );
+
+    assert_eq!(line["level"], "INFO");
+    assert_eq!(line["msg"], "query done");
+    assert_eq!(line["request_id"], "req-1");
+    assert_eq!(line["rows_out"], 42);
+    assert_eq!(line["cached"], false);
+    assert!(line["ts"].as_str().is_some_and(|ts| ts.ends_with('Z')));
+}
+
+#[test]
+fn only_sane_incoming_request_ids_are_accepted() {
+    assert_eq!(accept_request_id(Some(" abc-123 ")).as_deref(), Some("abc-123"));
+    assert_eq!(accept_request_id(Some("")), None);
+    assert_eq!(accept_request_id(Some("a\"}\n{\"level\":\"ERROR")), None);
+    assert_eq!(accept_request_id(Some(&"x".repeat(200))), None);
+    assert_eq!(accept_request_id(None), None);
+}
+
+#[actix_web::test]
+async fn request_id_is_echoed_or_generated() {
+    let app = actix_test::init_service(
+        App::new()
+            .wrap(from_fn(server::middleware::request_id))
+            .route("/ok", web::get().to(HttpResponse::Ok))
+            .route("/missing", web::get().to(HttpResponse::NotFound)),
+    )
+    .await;
+
+    let res = actix_test::call_service(
+        &app,
+        actix_test::TestRequest::get()
+            .uri("/missing")
+            .insert_header(("X-Request-Id", "from-luzmo-1"))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(res.headers().get("X-Request-Id").unwrap(), "from-luzmo-1");
+
+    let res = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/ok").to_request()).await;
+    assert!(res.headers().get("X-Request-Id").is_some_and(|v| !v.is_empty()));
+}
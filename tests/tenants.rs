@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use actix_web::{test as actix_test, web, App};
+use luzmo_plugin::server;
+use luzmo_plugin::utils::credentials::{AllowAll, CredentialVerifier, Identity};
+use luzmo_plugin::utils::policies::RowPolicies;
+use luzmo_plugin::utils::signing::SigningConfig;
+use luzmo_plugin::utils::tenants::{Tenant, Tenants};
+use serde_json::Value;
+
+fn tenants() -> Tenants {
+    Tenants::new(vec![
+        Tenant {
+            id: "acme".into(),
+            datasets: vec!["demo".into()],
+            secrets: vec!["acme_secret".into()],
+        },
+        Tenant {
+            id: "globex".into(),
+            datasets: vec![],
+            secrets: vec!["globex_secret".into()],
+        },
+    ])
+}
+
+#[test]
+fn access_follows_tenant_datasets() {
+    let t = tenants();
+    let acme = Identity { tenant: Some("acme".into()), ..Identity::anonymous() };
+    let globex = Identity { tenant: Some("globex".into()), ..Identity::anonymous() };
+
+    assert!(t.can_access(&acme, "demo"));
+    assert!(!t.can_access(&globex, "demo"));
+    assert!(!t.can_access(&Identity::anonymous(), "demo"));
+    assert!(Tenants::default().can_access(&Identity::anonymous(), "demo"));
+}
+
+#[test]
+fn broken_or_empty_tenant_config_fails_closed() {
+    let dir = std::env::temp_dir().join(format!("tenants-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let broken = dir.join("broken.json");
+    std::fs::write(&broken, "[{ \"id\": ").unwrap();
+    assert!(Tenants::from_file(broken.to_str().unwrap()).is_err());
+    assert!(Tenants::from_file(dir.join("missing.json").to_str().unwrap()).is_err());
+
+    let empty = dir.join("empty.json");
+    std::fs::write(&empty, "[]").unwrap();
+    let none = Tenants::from_file(empty.to_str().unwrap()).unwrap();
+    let acme = Identity { tenant: Some("acme".into()), ..Identity::anonymous() };
+    assert!(none.is_multi_tenant());
+    assert!(!none.can_access(&acme, "demo"));
+    assert!(!none.can_access(&Identity::anonymous(), "demo"));
+}
+
+#[actix_web::test]
+async fn tenants_only_see_their_datasets() {
+    let verifier: Arc<dyn CredentialVerifier> = Arc::new(AllowAll);
+    let app = actix_test::init_service(
+        App::new()
+            .app_data(web::Data::from(verifier))
+            .app_data(web::Data::new(tenants()))
+            .app_data(web::Data::new(RowPolicies::default()))
+            .app_data(web::Data::new(SigningConfig::default()))
+            .route("/datasets", web::post().to(server::dataset::handle_datasets))
+            .route("/query", web::post().to(server::query::handle_query)),
+    )
+    .await;
+
+    for (secret, expected) in [("acme_secret", 1), ("globex_secret", 0)] {
+        let req = actix_test::TestRequest::post()
+            .uri("/datasets")
+            .insert_header(("X-Secret", secret))
+            .to_request();
+        let body: Vec<Value> = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body.len(), expected, "{}", secret);
+    }
+
+    let query = |secret: &'static str| {
+        actix_test::TestRequest::post()
+            .uri("/query")
+            .insert_header(("X-Secret", secret))
+            .set_payload(r#"{"dataset_id":"demo","limit":1}"#)
+            .to_request()
+    };
+    assert_eq!(actix_test::call_service(&app, query("acme_secret")).await.status(), 200);
+    assert_eq!(actix_test::call_service(&app, query("globex_secret")).await.status(), 404);
+    assert_eq!(actix_test::call_service(&app, query("unknown")).await.status(), 401);
+}
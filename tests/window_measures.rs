@@ -0,0 +1,53 @@
+use luzmo_plugin::engine::context::QueryContext;
+use luzmo_plugin::engine::execute::run;
+use luzmo_plugin::luzmo::types::{Column, QueryRequest};
+
+fn monthly(window: &str, size: Option<usize>) -> QueryRequest {
+    QueryRequest {
+        dataset_id: Some("demo".to_string()),
+        columns: Some(vec![
+            Column { id: "date".into(), level: Some("month".into()), ..Default::default() },
+            Column { id: "value".into(), aggregation: Some("sum".into()), ..Default::default() },
+            Column {
+                id: "value".into(),
+                aggregation: Some("sum".into()),
+                window: Some(window.into()),
+                window_size: size,
+                ..Default::default()
+            },
+        ]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn running_sum_accumulates_months() {
+    let rows = run(&monthly("running_sum", None), &QueryContext::default()).unwrap().0;
+    assert_eq!(rows.len(), 6);
+
+    let mut acc = 0.0;
+    for r in &rows {
+        acc += r[1].as_f64().unwrap();
+        assert!((r[2].as_f64().unwrap() - acc).abs() < 0.05);
+    }
+}
+
+#[test]
+fn difference_starts_empty_and_rank_covers_every_month() {
+    let rows = run(&monthly("difference", None), &QueryContext::default()).unwrap().0;
+    assert!(rows[0][2].is_null());
+    let expected = rows[1][1].as_f64().unwrap() - rows[0][1].as_f64().unwrap();
+    assert!((rows[1][2].as_f64().unwrap() - expected).abs() < 0.05);
+
+    let ranks = run(&monthly("rank", None), &QueryContext::default()).unwrap().0;
+    let mut seen: Vec<i64> = ranks.iter().map(|r| r[2].as_i64().unwrap()).collect();
+    seen.sort();
+    assert_eq!(seen, vec![1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn moving_avg_requires_window_size() {
+    assert!(run(&monthly("moving_avg", None), &QueryContext::default()).is_err());
+    assert!(run(&monthly("moving_avg", Some(3)), &QueryContext::default()).is_ok());
+    assert!(run(&monthly("median", None), &QueryContext::default()).is_err());
+}